@@ -29,6 +29,102 @@ pub struct Threepid {
 }
 
 
+/// Accepts flags as JSON bools, and as the integers 0 and 1,
+/// which synapse returns for some flags depending on its version and database.
+pub fn bool_or_int<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    use serde::de::{Error, Unexpected};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrInt {
+        Bool(bool),
+        Int(u64),
+    }
+
+    match BoolOrInt::deserialize(deserializer)? {
+        BoolOrInt::Bool(value) => Ok(value),
+        BoolOrInt::Int(0) => Ok(false),
+        BoolOrInt::Int(1) => Ok(true),
+        BoolOrInt::Int(value) => Err(D::Error::invalid_value(Unexpected::Unsigned(value), &"a boolean, 0 or 1")),
+    }
+}
+
+/// Defines an enum of string values, which keeps unknown values in an Other variant,
+/// so that responses of newer synapse versions can still be parsed.
+/// Parsing with FromStr only accepts the known values, for validating user input.
+macro_rules! string_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident => $value:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, Eq, PartialEq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A value unknown to synadminctl
+            Other(String),
+        }
+
+        impl $name {
+            /// All known values
+            pub const VARIANTS: &'static [&'static str] = &[$($value),*];
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Other(value) => value,
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($value => Ok($name::$variant),)*
+                    _ => Err(format!("unknown value {:?}, expected one of {}", s, Self::VARIANTS.join(", "))),
+                }
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                value.parse().unwrap_or($name::Other(value))
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <String as serde::Deserialize>::deserialize(deserializer).map($name::from)
+            }
+        }
+    };
+}
+
+string_enum! {
+    /// Order of paginated lists
+    pub enum Direction {
+        Forward => "f",
+        Backward => "b",
+    }
+}
+
+
 pub mod version {
     use ruma::api::ruma_api;
 
@@ -44,6 +140,7 @@ pub mod version {
 
         request: {}
 
+        #[derive(serde::Serialize)]
         response: {
             pub server_version: String,
             pub python_version: String,
@@ -80,6 +177,7 @@ pub mod identity_status {
 
         request: {}
 
+        #[derive(serde::Serialize)]
         response: {}
 
         // TODO: What kind of error is needed here?
@@ -98,14 +196,14 @@ pub mod list_accounts {
         pub name: ruma::UserId,
         // TODO: this isn't named as optional in the spec, but missing from the responses
         pub password_hash: Option<String>,
-        // TODO: why not bool?
-        pub is_guest: js_int::UInt,
-        // TODO: why not bool?
-        pub admin: js_int::UInt,
+        #[serde(deserialize_with = "crate::bool_or_int")]
+        pub is_guest: bool,
+        #[serde(deserialize_with = "crate::bool_or_int")]
+        pub admin: bool,
         // TODO: what is this field? It's null in the examples
         pub user_type: Option<String>,
-        // TODO: why not bool?
-        pub deactivated: js_int::UInt,
+        #[serde(deserialize_with = "crate::bool_or_int")]
+        pub deactivated: bool,
         pub displayname: Option<String>,
         pub avatar_url: Option<String>,
     }
@@ -122,11 +220,11 @@ pub mod list_accounts {
 
         #[derive(Default)]
         request: {
-            /// TODO: this should be treated as opaque, i.e. a newtype, so that only values returned from responses can be used here
-            /// Offset in the returned list. Defaults to 0.
+            /// Offset in the returned list, as returned in next_token. Defaults to 0.
+            /// Kept as opaque string like next_token, so that any token synapse returns can be passed back.
             #[serde(skip_serializing_if="Option::is_none")]
             #[ruma_api(query)]
-            pub from: Option<js_int::UInt>,
+            pub from: Option<String>,
             /// Maximum amount of users to return. Defaults to 100.
             #[serde(skip_serializing_if="Option::is_none")]
             #[ruma_api(query)]
@@ -149,6 +247,7 @@ pub mod list_accounts {
             pub deactivated: Option<bool>,
         }
 
+        #[derive(serde::Serialize)]
         response: {
             pub users: Vec<UserDetails>,
             /// To paginate, check for next_token and if present, call the endpoint again with from set to the value of next_token. This will return a new page.
@@ -166,6 +265,17 @@ pub mod list_accounts {
         }
     }
 
+    impl crate::Paginated for Request {
+        type Item = UserDetails;
+
+        fn next_page(&self, response: Response) -> (Vec<UserDetails>, Option<Self>) {
+            // passed back as is, so that a token in an unexpected format cannot end the listing early
+            let next_request = response.next_token
+                .map(|from| Self { from: Some(from), ..self.clone() });
+            (response.users, next_request)
+        }
+    }
+
 }
 
 /// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#list-room-api
@@ -173,6 +283,62 @@ pub mod list_rooms {
     use ruma::api::ruma_api;
     use serde::{Serialize, Deserialize};
 
+    string_enum! {
+        /// Field by which rooms are ordered, the default is name
+        pub enum RoomOrderBy {
+            Name => "name",
+            CanonicalAlias => "canonical_alias",
+            JoinedMembers => "joined_members",
+            JoinedLocalMembers => "joined_local_members",
+            Version => "version",
+            Creator => "creator",
+            Encryption => "encryption",
+            Federatable => "federatable",
+            Public => "public",
+            JoinRules => "join_rules",
+            GuestAccess => "guest_access",
+            HistoryVisibility => "history_visibility",
+            StateEvents => "state_events",
+        }
+    }
+
+    string_enum! {
+        /// Who can join a room
+        pub enum JoinRule {
+            Public => "public",
+            Knock => "knock",
+            Invite => "invite",
+            Private => "private",
+            Restricted => "restricted",
+            KnockRestricted => "knock_restricted",
+        }
+    }
+
+    string_enum! {
+        /// Whether guest users can join a room
+        pub enum GuestAccess {
+            CanJoin => "can_join",
+            Forbidden => "forbidden",
+        }
+    }
+
+    string_enum! {
+        /// Who can read the history of a room
+        pub enum HistoryVisibility {
+            Invited => "invited",
+            Joined => "joined",
+            Shared => "shared",
+            WorldReadable => "world_readable",
+        }
+    }
+
+    string_enum! {
+        /// End-to-end encryption algorithm of a room
+        pub enum Encryption {
+            MegolmV1AesSha2 => "m.megolm.v1.aes-sha2",
+        }
+    }
+
     #[derive(Serialize, Deserialize, Clone, Debug)]
     pub struct RoomDetails {
         pub room_id: ruma::RoomId,
@@ -183,16 +349,21 @@ pub mod list_rooms {
         pub version: String,
         #[serde(deserialize_with = "ruma::serde::empty_string_as_none")]
         pub creator: Option<ruma::UserId>,
-        pub encryption: Option<String>,
+        /// None if the room is not encrypted
+        pub encryption: Option<Encryption>,
+        #[serde(deserialize_with = "crate::bool_or_int")]
         pub federatable: bool,
+        #[serde(deserialize_with = "crate::bool_or_int")]
         pub public: bool,
-        // TODO: make enum
-        pub join_rules: Option<String>,
-        // TODO: make enum
-        pub guest_access: Option<String>,
-        // TODO: make enum
-        pub history_visibility: Option<String>,
+        pub join_rules: Option<JoinRule>,
+        pub guest_access: Option<GuestAccess>,
+        pub history_visibility: Option<HistoryVisibility>,
         pub state_events: js_int::UInt,
+        // only returned for single rooms by room_details
+        pub avatar: Option<String>,
+        pub topic: Option<String>,
+        pub room_type: Option<String>,
+        pub joined_local_devices: Option<js_int::UInt>,
     }
 
     ruma_api! {
@@ -215,21 +386,21 @@ pub mod list_rooms {
             #[serde(skip_serializing_if="Option::is_none")]
             #[ruma_api(query)]
             pub limit: Option<js_int::UInt>,
-            // TODO: enum
+            /// Defaults to name
             #[serde(skip_serializing_if="Option::is_none")]
             #[ruma_api(query)]
-            pub order_by: Option<String>,
-            // TODO: enum
+            pub order_by: Option<RoomOrderBy>,
+            /// Defaults to forward
             #[serde(skip_serializing_if="Option::is_none")]
             #[ruma_api(query)]
-            pub dir: Option<String>,
+            pub dir: Option<super::Direction>,
             /// Filter rooms by their room name. Search term can be contained in any part of the room name. Defaults to no filtering.
-            // TODO: enum
             #[serde(skip_serializing_if="Option::is_none")]
             #[ruma_api(query)]
             pub search_term: Option<String>,
         }
 
+        #[derive(serde::Serialize)]
         response: {
             pub rooms: Vec<RoomDetails>,
             pub offset: js_int::UInt,
@@ -247,6 +418,16 @@ pub mod list_rooms {
         }
     }
 
+    impl crate::Paginated for Request {
+        type Item = RoomDetails;
+
+        fn next_page(&self, response: Response) -> (Vec<RoomDetails>, Option<Self>) {
+            let next_request = response.next_batch
+                .map(|from| Self { from: Some(from), ..self.clone() });
+            (response.rooms, next_request)
+        }
+    }
+
 }
 
 /// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#query-user-account
@@ -268,16 +449,15 @@ pub mod query_user {
             pub user_id: ruma::UserId,
         }
 
+        #[derive(serde::Serialize)]
         response: {
             pub displayname: Option<String>,
             pub threepids: Option<Vec<super::Threepid>>,
             pub avatar_url: Option<String>,
-            // TODO: this is returned as int, while the doc shows a bool
-            // I could convert, or investigate further whether there are other values, or fix it upstream
-            pub admin: js_int::UInt,
-            // TODO: this is returned as int, while the doc shows a bool
-            // I could convert, or investigate further whether there are other values, or fix it upstream
-            pub deactivated: js_int::UInt,
+            #[serde(deserialize_with = "crate::bool_or_int")]
+            pub admin: bool,
+            #[serde(deserialize_with = "crate::bool_or_int")]
+            pub deactivated: bool,
         }
 
         error: ruma::api::client::Error
@@ -309,6 +489,7 @@ pub mod list_joined_rooms {
             pub user_id: ruma::UserId,
         }
 
+        #[derive(serde::Serialize)]
         response: {
             pub joined_rooms: Vec<ruma::RoomId>,
             /// number of rooms
@@ -325,7 +506,7 @@ pub mod list_joined_rooms {
     }
 }
 
-// TODO: This endpoint is deprecated:
+// This endpoint is deprecated in favour of delete_room:
 // https://github.com/matrix-org/synapse/blob/master/docs/admin_api/purge_room.md
 pub mod purge_room {
     use ruma::api::ruma_api;
@@ -344,6 +525,7 @@ pub mod purge_room {
             pub room_id: ruma::RoomId,
         }
 
+        #[derive(serde::Serialize)]
         response: {}
 
         error: ruma::api::client::Error
@@ -357,6 +539,179 @@ pub mod purge_room {
 }
 
 
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#version-2-new-version
+pub mod delete_room {
+    use ruma::api::ruma_api;
+    use serde::{Serialize, Deserialize};
+
+    string_enum! {
+        /// Progress of a room deletion
+        pub enum Status {
+            Scheduled => "scheduled",
+            ShuttingDown => "shutting_down",
+            Purging => "purging",
+            Complete => "complete",
+            Failed => "failed",
+        }
+    }
+
+    impl Status {
+        /// Whether the deletion has stopped, successfully or not
+        pub fn is_finished(&self) -> bool {
+            matches!(self, Status::Complete | Status::Failed)
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+    pub struct ShutdownRoom {
+        #[serde(default)]
+        pub kicked_users: Vec<ruma::UserId>,
+        #[serde(default)]
+        pub failed_to_kick_users: Vec<ruma::UserId>,
+        #[serde(default)]
+        pub local_aliases: Vec<ruma::RoomAliasId>,
+        pub new_room_id: Option<ruma::RoomId>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct DeleteStatus {
+        pub delete_id: String,
+        pub status: Status,
+        /// only set if the status is failed
+        pub error: Option<String>,
+        #[serde(default)]
+        pub shutdown_room: ShutdownRoom,
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "delete room endpoint, deleting in the background",
+            method: DELETE,
+            name: "delete_room",
+            path: "/_synapse/admin/v2/rooms/:room_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+            /// If set, a new room is created with this user as creator, into which all local users are moved.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub new_room_user_id: Option<ruma::UserId>,
+            /// Name of the new room, defaults to "Content Violation Notification"
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub room_name: Option<String>,
+            /// First message in the new room
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub message: Option<String>,
+            /// Prevent future attempts to join the room. Defaults to false.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub block: Option<bool>,
+            /// Remove all traces of the room from the database. Defaults to true.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub purge: Option<bool>,
+            /// Purge even if local users cannot be removed from the room. Defaults to false.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub force_purge: Option<bool>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            /// To query the progress with delete_status
+            pub delete_id: String,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self {
+                room_id,
+                new_room_user_id: None,
+                room_name: None,
+                message: None,
+                block: None,
+                purge: None,
+                force_purge: None,
+            }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#query-by-delete_id
+pub mod delete_status {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "status of a room deletion",
+            method: GET,
+            name: "delete_status",
+            path: "/_synapse/admin/v2/rooms/delete_status/:delete_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub delete_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub status: super::delete_room::Status,
+            /// only set if the status is failed
+            pub error: Option<String>,
+            #[serde(default)]
+            pub shutdown_room: super::delete_room::ShutdownRoom,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(delete_id: String) -> Self {
+            Self { delete_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#query-by-room_id
+pub mod room_delete_status {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "status of all deletions of a room",
+            method: GET,
+            name: "room_delete_status",
+            path: "/_synapse/admin/v2/rooms/:room_id/delete_status",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub results: Vec<super::delete_room::DeleteStatus>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
 
 /// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#create-or-modify-account
 pub mod create_modify_account {
@@ -404,13 +759,14 @@ pub mod create_modify_account {
 
         // TODO: this response reverse-engineered and not documented, should all of those be required?
         // Alternative: https://serde.rs/attr-flatten.html
+        #[derive(serde::Serialize)]
         response: {
             pub name: ruma::UserId,
             pub password_hash: String,
-            // TODO: this is not returned as bool…?
-            pub is_guest: js_int::UInt,
-            // TODO: this is not returned as bool…?
-            pub admin: js_int::UInt,
+            #[serde(deserialize_with = "crate::bool_or_int")]
+            pub is_guest: bool,
+            #[serde(deserialize_with = "crate::bool_or_int")]
+            pub admin: bool,
             // TODO: not sure if this should be Option<js::UInt>
             // this is present but can be null, therefore optional
             pub consent_version: Option<String>,
@@ -423,8 +779,8 @@ pub mod create_modify_account {
             pub creation_ts: js_int::UInt,
             // this is present but can be null, therefore optional
             pub user_type: Option<String>,
-            // TODO: this is not returned as bool…?
-            pub deactivated: js_int::UInt,
+            #[serde(deserialize_with = "crate::bool_or_int")]
+            pub deactivated: bool,
             pub displayname: Option<String>,
             // this is present but can be null, therefore optional
             pub avatar_url: Option<String>,
@@ -494,6 +850,7 @@ pub mod reset_password {
             pub logout_devices: Option<bool>,
         }
 
+        #[derive(serde::Serialize)]
         response: {}
 
         error: ruma::api::client::Error
@@ -526,6 +883,7 @@ pub mod user_is_admin {
             pub user_id: ruma::UserId,
         }
 
+        #[derive(serde::Serialize)]
         response: {
             pub admin: bool,
         }
@@ -539,3 +897,1608 @@ pub mod user_is_admin {
         }
     }
 }
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/usage/administration/admin_api/registration_tokens.md
+pub mod registration_tokens {
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct RegistrationToken {
+        pub token: String,
+        /// How often the token can be used to complete a registration, None means unlimited
+        pub uses_allowed: Option<js_int::UInt>,
+        /// Registrations in progress using this token
+        pub pending: js_int::UInt,
+        /// Completed registrations using this token
+        pub completed: js_int::UInt,
+        /// Milliseconds since the unix epoch after which the token is not valid anymore, None means never
+        pub expiry_time: Option<js_int::UInt>,
+    }
+
+    impl RegistrationToken {
+        /// None means unlimited
+        pub fn remaining_uses(&self) -> Option<js_int::UInt> {
+            self.uses_allowed.map(|uses_allowed|
+                uses_allowed.saturating_sub(self.pending).saturating_sub(self.completed))
+        }
+    }
+}
+
+pub mod list_registration_tokens {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "list registration tokens endpoint",
+            method: GET,
+            name: "list_registration_tokens",
+            path: "/_synapse/admin/v1/registration_tokens",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        #[derive(Default)]
+        request: {
+            /// If true, only list valid tokens, if false, only list expired or used up ones. Defaults to listing all tokens.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub valid: Option<bool>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub registration_tokens: Vec<super::registration_tokens::RegistrationToken>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new() -> Self {
+            Default::default()
+        }
+    }
+}
+
+pub mod get_registration_token {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "get registration token endpoint",
+            method: GET,
+            name: "get_registration_token",
+            path: "/_synapse/admin/v1/registration_tokens/:token",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub token: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            #[ruma_api(body)]
+            pub registration_token: super::registration_tokens::RegistrationToken,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(token: String) -> Self {
+            Self { token }
+        }
+    }
+}
+
+pub mod create_registration_token {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "create registration token endpoint",
+            method: POST,
+            name: "create_registration_token",
+            path: "/_synapse/admin/v1/registration_tokens/new",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        #[derive(Default)]
+        request: {
+            /// Defaults to a random token of the given length
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub token: Option<String>,
+            /// Defaults to unlimited uses
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub uses_allowed: Option<js_int::UInt>,
+            /// Milliseconds since the unix epoch, defaults to never expiring
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub expiry_time: Option<js_int::UInt>,
+            /// Length of the generated token, if none is given. Defaults to 16.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub length: Option<js_int::UInt>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            #[ruma_api(body)]
+            pub registration_token: super::registration_tokens::RegistrationToken,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new() -> Self {
+            Default::default()
+        }
+    }
+}
+
+pub mod update_registration_token {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "update registration token endpoint",
+            method: PUT,
+            name: "update_registration_token",
+            path: "/_synapse/admin/v1/registration_tokens/:token",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub token: String,
+            // None keeps the current value, Some(None) sets it to null, i.e. unlimited
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub uses_allowed: Option<Option<js_int::UInt>>,
+            // None keeps the current value, Some(None) sets it to null, i.e. never expiring
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub expiry_time: Option<Option<js_int::UInt>>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            #[ruma_api(body)]
+            pub registration_token: super::registration_tokens::RegistrationToken,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(token: String) -> Self {
+            Self {
+                token,
+                uses_allowed: None,
+                expiry_time: None,
+            }
+        }
+    }
+}
+
+pub mod delete_registration_token {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "delete registration token endpoint",
+            method: DELETE,
+            name: "delete_registration_token",
+            path: "/_synapse/admin/v1/registration_tokens/:token",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub token: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(token: String) -> Self {
+            Self { token }
+        }
+    }
+}
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#user-devices
+pub mod devices {
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct Device {
+        pub device_id: String,
+        pub display_name: Option<String>,
+        /// None if the device has not been used since synapse started to track it
+        pub last_seen_ip: Option<String>,
+        /// Milliseconds since the unix epoch
+        pub last_seen_ts: Option<js_int::UInt>,
+        pub user_id: ruma::UserId,
+    }
+}
+
+pub mod list_devices {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "list devices of a user endpoint",
+            method: GET,
+            name: "list_devices",
+            path: "/_synapse/admin/v2/users/:user_id/devices",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub devices: Vec<super::devices::Device>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId) -> Self {
+            Self { user_id }
+        }
+    }
+}
+
+pub mod get_device {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "get a device of a user endpoint",
+            method: GET,
+            name: "get_device",
+            path: "/_synapse/admin/v2/users/:user_id/devices/:device_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            #[ruma_api(path)]
+            pub device_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            #[ruma_api(body)]
+            pub device: super::devices::Device,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId, device_id: String) -> Self {
+            Self { user_id, device_id }
+        }
+    }
+}
+
+pub mod update_device {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "update the display name of a device of a user endpoint",
+            method: PUT,
+            name: "update_device",
+            path: "/_synapse/admin/v2/users/:user_id/devices/:device_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            #[ruma_api(path)]
+            pub device_id: String,
+            pub display_name: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId, device_id: String, display_name: String) -> Self {
+            Self { user_id, device_id, display_name }
+        }
+    }
+}
+
+pub mod delete_device {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "delete a device of a user endpoint, invalidating its access token",
+            method: DELETE,
+            name: "delete_device",
+            path: "/_synapse/admin/v2/users/:user_id/devices/:device_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            #[ruma_api(path)]
+            pub device_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId, device_id: String) -> Self {
+            Self { user_id, device_id }
+        }
+    }
+}
+
+pub mod delete_devices {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "delete multiple devices of a user endpoint, invalidating their access tokens",
+            method: POST,
+            name: "delete_devices",
+            path: "/_synapse/admin/v2/users/:user_id/delete_devices",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            pub devices: Vec<String>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId, devices: Vec<String>) -> Self {
+            Self { user_id, devices }
+        }
+    }
+}
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#deactivate-account
+pub mod deactivate_account {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "deactivate account endpoint",
+            method: POST,
+            name: "deactivate_account",
+            path: "/_synapse/admin/v1/deactivate/:user_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            /// Additionally remove the profile and mark the messages of the user as erased,
+            /// so that they are not shown to users joining later on. Defaults to false.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub erase: Option<bool>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            /// "success" if the threepids of the user were unbound from all identity servers,
+            /// "no-support" if an identity server did not support unbinding
+            pub id_server_unbind_result: String,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId, erase: Option<bool>) -> Self {
+            Self { user_id, erase }
+        }
+    }
+}
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#list-all-media-in-a-room
+pub mod room_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "list media of a room endpoint",
+            method: GET,
+            name: "room_media",
+            path: "/_synapse/admin/v1/room/:room_id/media",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            /// mxc:// URIs of media uploaded to this server
+            pub local: Vec<String>,
+            /// mxc:// URIs of media uploaded to other servers
+            pub remote: Vec<String>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#list-media-of-a-user
+pub mod list_user_media {
+    use ruma::api::ruma_api;
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct Media {
+        pub media_id: String,
+        pub media_type: String,
+        /// Size in bytes
+        pub media_length: js_int::UInt,
+        pub upload_name: Option<String>,
+        /// Milliseconds since the unix epoch
+        pub created_ts: js_int::UInt,
+        /// Milliseconds since the unix epoch
+        pub last_access_ts: Option<js_int::UInt>,
+        /// User who quarantined the media, None if the media is not quarantined
+        pub quarantined_by: Option<String>,
+        #[serde(deserialize_with = "crate::bool_or_int")]
+        pub safe_from_quarantine: bool,
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "list media of a user endpoint",
+            method: GET,
+            name: "list_user_media",
+            path: "/_synapse/admin/v1/users/:user_id/media",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            /// Offset in the returned list. Defaults to 0.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub from: Option<js_int::UInt>,
+            /// Maximum amount of media to return. Defaults to 100.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub limit: Option<js_int::UInt>,
+            /// Field to order by, e.g. media_length or created_ts. Defaults to created_ts.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub order_by: Option<String>,
+            /// Defaults to forward
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub dir: Option<super::Direction>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub media: Vec<Media>,
+            pub next_token: Option<js_int::UInt>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId) -> Self {
+            Self {
+                user_id,
+                from: None,
+                limit: None,
+                order_by: None,
+                dir: None,
+            }
+        }
+    }
+
+    impl crate::Paginated for Request {
+        type Item = Media;
+
+        fn next_page(&self, response: Response) -> (Vec<Media>, Option<Self>) {
+            let next_request = response.next_token
+                .map(|from| Self { from: Some(from), ..self.clone() });
+            (response.media, next_request)
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#delete-media-uploaded-by-a-user
+pub mod delete_user_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "delete media of a user endpoint",
+            method: DELETE,
+            name: "delete_user_media",
+            path: "/_synapse/admin/v1/users/:user_id/media",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+            /// Offset in the list of media of the user. Defaults to 0.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub from: Option<js_int::UInt>,
+            /// Maximum amount of media to delete. Defaults to 100.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub limit: Option<js_int::UInt>,
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub order_by: Option<String>,
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub dir: Option<super::Direction>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub deleted_media: Vec<String>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId) -> Self {
+            Self {
+                user_id,
+                from: None,
+                limit: None,
+                order_by: None,
+                dir: None,
+            }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#delete-a-specific-local-media
+pub mod delete_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "delete a local media endpoint",
+            method: DELETE,
+            name: "delete_media",
+            path: "/_synapse/admin/v1/media/:server_name/:media_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// Has to be the name of this server
+            #[ruma_api(path)]
+            pub server_name: String,
+            #[ruma_api(path)]
+            pub media_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub deleted_media: Vec<String>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(server_name: String, media_id: String) -> Self {
+            Self { server_name, media_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#delete-local-media-by-date-or-size
+pub mod delete_local_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "delete local media by date or size endpoint",
+            method: POST,
+            name: "delete_local_media",
+            path: "/_synapse/admin/v1/media/:server_name/delete",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// Has to be the name of this server
+            #[ruma_api(path)]
+            pub server_name: String,
+            /// Delete media last accessed before this time, in milliseconds since the unix epoch
+            #[ruma_api(query)]
+            pub before_ts: js_int::UInt,
+            /// Only delete media larger than this amount of bytes. Defaults to 0.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub size_gt: Option<js_int::UInt>,
+            /// Whether to keep media used as avatar of a user or room. Defaults to true.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub keep_profiles: Option<bool>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub deleted_media: Vec<String>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(server_name: String, before_ts: js_int::UInt) -> Self {
+            Self {
+                server_name,
+                before_ts,
+                size_gt: None,
+                keep_profiles: None,
+            }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#purge-remote-media-api
+pub mod purge_media_cache {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "purge cached remote media endpoint",
+            method: POST,
+            name: "purge_media_cache",
+            path: "/_synapse/admin/v1/purge_media_cache",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// Purge media last accessed before this time, in milliseconds since the unix epoch
+            #[ruma_api(query)]
+            pub before_ts: js_int::UInt,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            /// Amount of purged media
+            pub deleted: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(before_ts: js_int::UInt) -> Self {
+            Self { before_ts }
+        }
+    }
+}
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#quarantining-media-by-id
+pub mod quarantine_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "quarantine a media endpoint",
+            method: POST,
+            name: "quarantine_media",
+            path: "/_synapse/admin/v1/media/quarantine/:server_name/:media_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub server_name: String,
+            #[ruma_api(path)]
+            pub media_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(server_name: String, media_id: String) -> Self {
+            Self { server_name, media_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#remove-media-from-quarantine-by-id
+pub mod unquarantine_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "remove a media from quarantine endpoint",
+            method: POST,
+            name: "unquarantine_media",
+            path: "/_synapse/admin/v1/media/unquarantine/:server_name/:media_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub server_name: String,
+            #[ruma_api(path)]
+            pub media_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(server_name: String, media_id: String) -> Self {
+            Self { server_name, media_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#quarantining-media-in-a-room
+pub mod quarantine_room_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "quarantine all media of a room endpoint",
+            method: POST,
+            name: "quarantine_room_media",
+            path: "/_synapse/admin/v1/room/:room_id/media/quarantine",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub num_quarantined: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#quarantining-all-media-of-a-user
+pub mod quarantine_user_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "quarantine all media of a user endpoint",
+            method: POST,
+            name: "quarantine_user_media",
+            path: "/_synapse/admin/v1/user/:user_id/media/quarantine",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub num_quarantined: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId) -> Self {
+            Self { user_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#protecting-media-from-being-quarantined
+pub mod protect_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "protect a media from quarantine endpoint",
+            method: POST,
+            name: "protect_media",
+            path: "/_synapse/admin/v1/media/protect/:media_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// Only media uploaded to this server can be protected
+            #[ruma_api(path)]
+            pub media_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(media_id: String) -> Self {
+            Self { media_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/media_admin_api.md#unprotecting-media-from-being-quarantined
+pub mod unprotect_media {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "unprotect a media from quarantine endpoint",
+            method: POST,
+            name: "unprotect_media",
+            path: "/_synapse/admin/v1/media/unprotect/:media_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// Only media uploaded to this server can be protected
+            #[ruma_api(path)]
+            pub media_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(media_id: String) -> Self {
+            Self { media_id }
+        }
+    }
+}
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/event_reports.md
+pub mod event_reports {
+    use ruma::api::ruma_api;
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct EventReport {
+        /// To query the details with event_report
+        pub id: js_int::UInt,
+        /// Milliseconds since the unix epoch
+        pub received_ts: js_int::UInt,
+        /// The user who reported the event
+        pub user_id: ruma::UserId,
+        pub room_id: ruma::RoomId,
+        /// Name of the room
+        pub name: Option<String>,
+        pub canonical_alias: Option<ruma::RoomAliasId>,
+        pub event_id: ruma::EventId,
+        /// The user who sent the reported event
+        pub sender: ruma::UserId,
+        /// From -100 for most offensive to 0 for inoffensive
+        pub score: Option<js_int::Int>,
+        pub reason: Option<String>,
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "list event reports endpoint",
+            method: GET,
+            name: "event_reports",
+            path: "/_synapse/admin/v1/event_reports",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        #[derive(Default)]
+        request: {
+            /// Offset in the returned list. Defaults to 0.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub from: Option<js_int::UInt>,
+            /// Maximum amount of reports to return. Defaults to 100.
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub limit: Option<js_int::UInt>,
+            /// Backward for newest first, which is the default
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub dir: Option<super::Direction>,
+            /// Only return reports of users whose ID contains this value
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub user_id: Option<String>,
+            /// Only return reports of rooms whose ID contains this value
+            #[serde(skip_serializing_if="Option::is_none")]
+            #[ruma_api(query)]
+            pub room_id: Option<String>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub event_reports: Vec<EventReport>,
+            pub next_token: Option<js_int::UInt>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new() -> Self {
+            Default::default()
+        }
+    }
+
+    impl crate::Paginated for Request {
+        type Item = EventReport;
+
+        fn next_page(&self, response: Response) -> (Vec<EventReport>, Option<Self>) {
+            let next_request = response.next_token
+                .map(|from| Self { from: Some(from), ..self.clone() });
+            (response.event_reports, next_request)
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/event_reports.md#show-details-of-a-specific-event-report
+pub mod event_report {
+    use ruma::api::ruma_api;
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct EventReportDetails {
+        #[serde(flatten)]
+        pub report: super::event_reports::EventReport,
+        /// The reported event
+        pub event_json: serde_json::Value,
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "event report details endpoint",
+            method: GET,
+            name: "event_report",
+            path: "/_synapse/admin/v1/event_reports/:report_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub report_id: js_int::UInt,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            #[ruma_api(body)]
+            pub details: EventReportDetails,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(report_id: js_int::UInt) -> Self {
+            Self { report_id }
+        }
+    }
+}
+
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#room-details-api
+pub mod room_details {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "room details endpoint",
+            method: GET,
+            name: "room_details",
+            path: "/_synapse/admin/v1/rooms/:room_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            #[ruma_api(body)]
+            pub room: super::list_rooms::RoomDetails,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#room-members-api
+pub mod room_members {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "room members endpoint",
+            method: GET,
+            name: "room_members",
+            path: "/_synapse/admin/v1/rooms/:room_id/members",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub members: Vec<ruma::UserId>,
+            pub total: js_int::UInt,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#room-state-api
+pub mod room_state {
+    use ruma::api::ruma_api;
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct StateEvent {
+        #[serde(rename = "type")]
+        pub event_type: String,
+        pub state_key: String,
+        pub sender: ruma::UserId,
+        pub event_id: ruma::EventId,
+        /// Milliseconds since the unix epoch
+        pub origin_server_ts: js_int::UInt,
+        /// Differs per event type, so it is left untyped
+        pub content: serde_json::Value,
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "room state endpoint",
+            method: GET,
+            name: "room_state",
+            path: "/_synapse/admin/v1/rooms/:room_id/state",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub state: Vec<StateEvent>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#block-room-api
+pub mod block_room {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "block or unblock room endpoint",
+            method: PUT,
+            name: "block_room",
+            path: "/_synapse/admin/v1/rooms/:room_id/block",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// The room does not need to be known to this server
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+            /// false to unblock the room
+            pub block: bool,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub block: bool,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId, block: bool) -> Self {
+            Self { room_id, block }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#get-block-status
+pub mod room_block_status {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "room block status endpoint",
+            method: GET,
+            name: "room_block_status",
+            path: "/_synapse/admin/v1/rooms/:room_id/block",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub block: bool,
+            /// The user who blocked the room, only set if it is blocked
+            pub user_id: Option<ruma::UserId>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self { room_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/rooms.md#make-room-admin-api
+pub mod make_room_admin {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "make a local user admin of a room endpoint",
+            method: POST,
+            name: "make_room_admin",
+            path: "/_synapse/admin/v1/rooms/:room_id_or_alias/make_room_admin",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// A local user with power to change the power levels has to be in the room
+            #[ruma_api(path)]
+            pub room_id_or_alias: ruma::RoomIdOrAliasId,
+            /// Local user to become room admin, joining the room if necessary. Defaults to the requesting user.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub user_id: Option<ruma::UserId>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {}
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id_or_alias: ruma::RoomIdOrAliasId, user_id: Option<ruma::UserId>) -> Self {
+            Self { room_id_or_alias, user_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/room_membership.md
+pub mod join_room {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "force a local user to join a room endpoint",
+            method: POST,
+            name: "join_room",
+            path: "/_synapse/admin/v1/join/:room_id_or_alias",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            /// The requesting user has to be in the room, unless it is public
+            #[ruma_api(path)]
+            pub room_id_or_alias: ruma::RoomIdOrAliasId,
+            pub user_id: ruma::UserId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub room_id: ruma::RoomId,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id_or_alias: ruma::RoomIdOrAliasId, user_id: ruma::UserId) -> Self {
+            Self { room_id_or_alias, user_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/purge_history_api.md
+pub mod purge_history {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "purge room history endpoint, purging in the background",
+            method: POST,
+            name: "purge_history",
+            // the event to purge up to can also be given as additional path segment,
+            // which is the same as purge_up_to_event_id
+            path: "/_synapse/admin/v1/purge_history/:room_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub room_id: ruma::RoomId,
+            /// Also purge events sent by local users. Defaults to false, which keeps them as backup.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub delete_local_events: Option<bool>,
+            /// Purge all events before this event
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub purge_up_to_event_id: Option<ruma::EventId>,
+            /// Purge all events before this time, in milliseconds since the unix epoch.
+            /// Ignored if purge_up_to_event_id is set.
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub purge_up_to_ts: Option<js_int::UInt>,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            /// To query the progress with purge_history_status
+            pub purge_id: String,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(room_id: ruma::RoomId) -> Self {
+            Self {
+                room_id,
+                delete_local_events: None,
+                purge_up_to_event_id: None,
+                purge_up_to_ts: None,
+            }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/purge_history_api.md#purge-status-query
+pub mod purge_history_status {
+    use ruma::api::ruma_api;
+
+    string_enum! {
+        /// Progress of a room history purge
+        pub enum Status {
+            Active => "active",
+            Complete => "complete",
+            Failed => "failed",
+        }
+    }
+
+    impl Status {
+        /// Whether the purge has stopped, successfully or not
+        pub fn is_finished(&self) -> bool {
+            matches!(self, Status::Complete | Status::Failed)
+        }
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "status of a room history purge",
+            method: GET,
+            name: "purge_history_status",
+            path: "/_synapse/admin/v1/purge_history_status/:purge_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub purge_id: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub status: Status,
+            /// only set if the status is failed
+            pub error: Option<String>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(purge_id: String) -> Self {
+            Self { purge_id }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/register_api.rst
+pub mod register_nonce {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "nonce for shared-secret registration endpoint",
+            method: GET,
+            name: "register_nonce",
+            path: "/_synapse/admin/v1/register",
+            rate_limited: false,
+            authentication: None,
+        }
+
+        request: {}
+
+        #[derive(serde::Serialize)]
+        response: {
+            /// Valid for 60 seconds, and only for a single registration
+            pub nonce: String,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new() -> Self {
+            Self { }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/register_api.rst
+pub mod register {
+    use ruma::api::ruma_api;
+
+    ruma_api! {
+        metadata: {
+            description: "shared-secret registration endpoint, which works without any existing account",
+            method: POST,
+            name: "register",
+            path: "/_synapse/admin/v1/register",
+            rate_limited: false,
+            authentication: None,
+        }
+
+        request: {
+            /// From register_nonce
+            pub nonce: String,
+            /// Localpart of the new user
+            pub username: String,
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub displayname: Option<String>,
+            pub password: String,
+            pub admin: bool,
+            #[serde(skip_serializing_if="Option::is_none")]
+            pub user_type: Option<String>,
+            /// Proof of knowing the shared secret, see crate::registration_mac
+            pub mac: String,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub user_id: ruma::UserId,
+            pub home_server: String,
+            pub device_id: String,
+            // not part of the command output, as it logs in as the new user
+            #[serde(skip_serializing)]
+            pub access_token: String,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(nonce: String, username: String, password: String, admin: bool, mac: String) -> Self {
+            Self {
+                nonce,
+                username,
+                displayname: None,
+                password,
+                admin,
+                user_type: None,
+                mac,
+            }
+        }
+    }
+}
+
+/// https://github.com/matrix-org/synapse/blob/master/docs/admin_api/user_admin_api.rst#query-current-sessions-for-a-user
+pub mod whois {
+    use ruma::api::ruma_api;
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct ConnectionInfo {
+        pub ip: String,
+        /// Milliseconds since the unix epoch
+        pub last_seen: js_int::UInt,
+        pub user_agent: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct SessionInfo {
+        pub connections: Vec<ConnectionInfo>,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug)]
+    pub struct DeviceInfo {
+        pub sessions: Vec<SessionInfo>,
+    }
+
+    ruma_api! {
+        metadata: {
+            description: "whois endpoint, showing from where a user connected",
+            method: GET,
+            name: "whois",
+            path: "/_synapse/admin/v1/whois/:user_id",
+            rate_limited: false,
+            authentication: AccessToken,
+        }
+
+        request: {
+            #[ruma_api(path)]
+            pub user_id: ruma::UserId,
+        }
+
+        #[derive(serde::Serialize)]
+        response: {
+            pub user_id: ruma::UserId,
+            /// By device ID
+            pub devices: std::collections::BTreeMap<String, DeviceInfo>,
+        }
+
+        error: ruma::api::client::Error
+    }
+
+    impl Request {
+        pub fn new(user_id: ruma::UserId) -> Self {
+            Self { user_id }
+        }
+    }
+
+    impl Response {
+        /// All connections of all sessions, together with their device ID
+        pub fn connections(&self) -> impl Iterator<Item = (&str, &ConnectionInfo)> {
+            self.devices.iter().flat_map(|(device_id, device)|
+                device.sessions.iter()
+                    .flat_map(|session| session.connections.iter())
+                    .map(move |connection| (device_id.as_str(), connection)))
+        }
+    }
+}
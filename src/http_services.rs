@@ -1,5 +1,9 @@
 use async_trait::async_trait;
-use std::convert::TryInto;
+use std::collections::VecDeque;
+use std::convert::{TryFrom, TryInto};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use crate::Service;
 
@@ -92,7 +96,6 @@ impl Service<http::Request<Vec<u8>>> for ReqwestService {
     type Error = anyhow::Error;
 
     async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
-        println!("http request: {:?}", http_request);
         let reqwest_request: reqwest::Request = http_request.try_into()?;
         let reqwest_response = self.client.execute(reqwest_request).await?;
         let mut http_response = http::Response::new(vec![]);
@@ -101,9 +104,521 @@ impl Service<http::Request<Vec<u8>>> for ReqwestService {
         let body = reqwest_response.bytes().await?;
         *http_response.body_mut() = body.to_vec();
 
-        println!("received http response: {:?}", http_response);
-        println!("decoded body: {:?}", std::str::from_utf8(http_response.body()).unwrap());
         Ok(http_response)
     }
 }
 
+
+const REDACTED: &str = "<redacted>";
+// fields of json request and response bodies which contain secrets
+// mac and nonce of shared-secret registration allow replaying the registration
+const SECRET_FIELDS: &[&str] = &["password", "new_password", "access_token", "mac", "nonce"];
+
+pub(crate) fn redact_uri(uri: &http::Uri) -> String {
+    let query = match uri.query() {
+        Some(query) => query,
+        None => return uri.to_string(),
+    };
+    let query: Vec<String> = query.split('&')
+        .map(|pair| if pair.starts_with("access_token=") {
+            format!("access_token={}", REDACTED)
+        } else {
+            pair.to_string()
+        })
+        .collect();
+    let uri = uri.to_string();
+    let path = uri.splitn(2, '?').next().unwrap_or_default();
+    format!("{}?{}", path, query.join("&"))
+}
+
+pub(crate) fn redact_headers(headers: &http::HeaderMap) -> Vec<(String, String)> {
+    headers.iter()
+        .map(|(name, value)| {
+            let value = if name == http::header::AUTHORIZATION {
+                REDACTED.to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+pub(crate) fn redact_body(body: &[u8]) -> String {
+    fn redact_value(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => for (key, value) in map.iter_mut() {
+                if SECRET_FIELDS.contains(&key.as_str()) {
+                    *value = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact_value(value);
+                }
+            },
+            serde_json::Value::Array(values) => values.iter_mut().for_each(redact_value),
+            _ => {},
+        }
+    }
+
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        },
+        // not json, e.g. an html error page of a reverse proxy
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+
+/// Logs requests and responses of the wrapped http service to stderr,
+/// with access tokens and passwords redacted.
+///
+/// Verbosity 0 logs nothing, 1 logs method, uri and status, and 2 additionally logs headers and bodies.
+#[derive(Clone, Debug)]
+pub struct LoggingService<S> {
+    inner: S,
+    verbosity: u8,
+}
+impl<S> LoggingService<S> {
+    pub fn new(inner: S, verbosity: u8) -> LoggingService<S> {
+        Self {
+            inner,
+            verbosity,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Service<http::Request<Vec<u8>>> for LoggingService<S>
+where
+    S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync,
+{
+    type Response = http::Response<Vec<u8>>;
+    type Error = anyhow::Error;
+
+    async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
+        if self.verbosity == 0 {
+            return self.inner.call(http_request).await;
+        }
+
+        let method = http_request.method().clone();
+        let uri = redact_uri(http_request.uri());
+        eprintln!("> {} {}", method, uri);
+        if self.verbosity >= 2 {
+            for (name, value) in redact_headers(http_request.headers()) {
+                eprintln!("> {}: {}", name, value);
+            }
+            if !http_request.body().is_empty() {
+                eprintln!("> {}", redact_body(http_request.body()));
+            }
+        }
+
+        let result = self.inner.call(http_request).await;
+
+        match &result {
+            Ok(http_response) => {
+                eprintln!("< {} {}: {}", method, uri, http_response.status());
+                if self.verbosity >= 2 {
+                    for (name, value) in redact_headers(http_response.headers()) {
+                        eprintln!("< {}: {}", name, value);
+                    }
+                    if !http_response.body().is_empty() {
+                        eprintln!("< {}", redact_body(http_response.body()));
+                    }
+                }
+            },
+            Err(error) => eprintln!("< {} {}: {}", method, uri, error),
+        }
+
+        result
+    }
+}
+
+
+
+/// Retries requests of the wrapped http service which failed transiently.
+///
+/// Rate limited requests (`M_LIMIT_EXCEEDED`) have not been processed by the server,
+/// and are retried after the `retry_after_ms` the server asked for, waiting at most 30 seconds.
+/// Server errors typically returned by reverse proxies (502, 503, 504) and connection errors
+/// are retried with exponential backoff and jitter. As the server might have processed those requests,
+/// this is only done for idempotent methods, unless enabled with `retry_non_idempotent`.
+#[derive(Clone, Debug)]
+pub struct RetryService<S> {
+    inner: S,
+    max_retries: u32,
+    retry_non_idempotent: bool,
+}
+impl<S> RetryService<S> {
+    pub fn new(inner: S, max_retries: u32) -> RetryService<S> {
+        Self {
+            inner,
+            max_retries,
+            retry_non_idempotent: false,
+        }
+    }
+
+    pub fn retry_non_idempotent(self, retry_non_idempotent: bool) -> RetryService<S> {
+        Self {
+            retry_non_idempotent,
+            ..self
+        }
+    }
+}
+
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+pub(crate) const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// Exponential backoff with jitter, i.e. a random delay between half and the full exponential delay
+pub(crate) fn backoff(retry: u32) -> Duration {
+    use rand::Rng;
+
+    let delay = BACKOFF_BASE.checked_mul(2u32.saturating_pow(retry)).unwrap_or(BACKOFF_MAX);
+    let delay = std::cmp::min(delay, BACKOFF_MAX);
+    rand::thread_rng().gen_range(delay / 2, delay)
+}
+
+pub(crate) fn rate_limit_delay(http_response: &http::Response<Vec<u8>>) -> Option<Duration> {
+    #[derive(serde::Deserialize)]
+    struct LimitExceeded {
+        errcode: String,
+        retry_after_ms: Option<u64>,
+    }
+
+    let error: LimitExceeded = serde_json::from_slice(http_response.body()).ok()?;
+    if error.errcode != "M_LIMIT_EXCEEDED" {
+        return None;
+    }
+    // a misconfigured server must not be able to block us for a day
+    error.retry_after_ms.map(|retry_after_ms| std::cmp::min(Duration::from_millis(retry_after_ms), BACKOFF_MAX))
+}
+
+// http::Request is not Clone, as its extensions might not be
+fn clone_request(http_request: &http::Request<Vec<u8>>) -> http::Request<Vec<u8>> {
+    let mut clone = http::Request::new(http_request.body().clone());
+    *clone.method_mut() = http_request.method().clone();
+    *clone.uri_mut() = http_request.uri().clone();
+    *clone.version_mut() = http_request.version();
+    *clone.headers_mut() = http_request.headers().clone();
+    clone
+}
+
+#[async_trait]
+impl<S> Service<http::Request<Vec<u8>>> for RetryService<S>
+where
+    S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync,
+{
+    type Response = http::Response<Vec<u8>>;
+    type Error = anyhow::Error;
+
+    async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
+        let retryable = self.retry_non_idempotent || http_request.method().is_idempotent();
+
+        for retry in 0..self.max_retries {
+            let result = self.inner.call(clone_request(&http_request)).await;
+
+            let delay = match &result {
+                Ok(http_response) if http_response.status() == http::StatusCode::TOO_MANY_REQUESTS =>
+                    rate_limit_delay(http_response).unwrap_or_else(|| backoff(retry)),
+                Ok(http_response) if retryable && matches!(http_response.status(),
+                    http::StatusCode::BAD_GATEWAY | http::StatusCode::SERVICE_UNAVAILABLE | http::StatusCode::GATEWAY_TIMEOUT) =>
+                    backoff(retry),
+                Err(_) if retryable => backoff(retry),
+                _ => return result,
+            };
+
+            smol::Timer::new(delay).await;
+        }
+
+        self.inner.call(http_request).await
+    }
+}
+
+
+/// A request which a `MockService` answers with a canned response.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    method: http::Method,
+    uri: String,
+    query: Vec<(String, String)>,
+    body: Option<serde_json::Value>,
+    status: http::StatusCode,
+    response_body: Vec<u8>,
+}
+impl Expectation {
+    /// Matches requests with the given method and absolute uri, including the exact query if one is given.
+    /// Percent-encoding is ignored, and responds with an empty 200 OK by default.
+    pub fn new(method: http::Method, uri: &str) -> Expectation {
+        let mut parts = uri.splitn(2, '?');
+        let path = parts.next().unwrap_or_default();
+        let query = parts.next().unwrap_or_default();
+        Self {
+            method,
+            uri: decode(path),
+            query: parse_query(query),
+            body: None,
+            status: http::StatusCode::OK,
+            response_body: vec![],
+        }
+    }
+
+    /// Only matches requests with a json body equal to the given one.
+    pub fn body(self, body: serde_json::Value) -> Expectation {
+        Self {
+            body: Some(body),
+            ..self
+        }
+    }
+
+    pub fn respond(self, status: http::StatusCode, response_body: impl Into<Vec<u8>>) -> Expectation {
+        Self {
+            status,
+            response_body: response_body.into(),
+            ..self
+        }
+    }
+
+    pub fn respond_json(self, status: http::StatusCode, response_body: serde_json::Value) -> Expectation {
+        self.respond(status, response_body.to_string())
+    }
+
+    fn matches(&self, http_request: &http::Request<Vec<u8>>) -> bool {
+        let uri = http_request.uri();
+        let request_uri = format!(
+            "{}://{}{}",
+            uri.scheme_str().unwrap_or_default(),
+            uri.authority().map(|authority| authority.as_str()).unwrap_or_default(),
+            uri.path(),
+        );
+
+        self.method == http_request.method()
+            && self.uri == decode(&request_uri)
+            && self.query == parse_query(uri.query().unwrap_or_default())
+            && self.body.as_ref().map_or(true, |body|
+                serde_json::from_slice::<serde_json::Value>(http_request.body()).ok().as_ref() == Some(body))
+    }
+}
+
+fn decode(input: &str) -> String {
+    percent_encoding::percent_decode_str(input).decode_utf8_lossy().into_owned()
+}
+
+// sorted, so that the order of query parameters does not matter
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+            let key = decode(parts.next().unwrap_or_default());
+            let value = decode(parts.next().unwrap_or_default());
+            (key, value)
+        })
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+/// An in-process stand-in for a homeserver, which answers requests with the response of the first
+/// matching `Expectation`, e.g. for tests without network access.
+/// Requests without a matching expectation fail like a connection error.
+#[derive(Clone, Debug, Default)]
+pub struct MockService {
+    expectations: Arc<Mutex<Vec<Expectation>>>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+impl MockService {
+    pub fn new() -> MockService {
+        Default::default()
+    }
+
+    pub fn expect(&self, expectation: Expectation) {
+        self.expectations.lock().unwrap().push(expectation);
+    }
+
+    /// Method and uri of all requests received so far, also of those without a matching expectation
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Service<http::Request<Vec<u8>>> for MockService {
+    type Response = http::Response<Vec<u8>>;
+    type Error = anyhow::Error;
+
+    async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
+        self.requests.lock().unwrap().push(format!("{} {}", http_request.method(), http_request.uri()));
+        let expectations = self.expectations.lock().unwrap();
+        let expectation = expectations.iter()
+            .find(|expectation| expectation.matches(&http_request))
+            .ok_or_else(|| anyhow::anyhow!("no expectation matches {} {}", http_request.method(), http_request.uri()))?;
+
+        let mut http_response = http::Response::new(expectation.response_body.clone());
+        *http_response.status_mut() = expectation.status;
+        Ok(http_response)
+    }
+}
+
+
+/// A type-erased http service, for choosing between differently composed services at runtime.
+#[derive(Clone)]
+pub struct BoxService {
+    inner: Arc<dyn Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync>,
+}
+impl BoxService {
+    pub fn new<S>(http_service: S) -> BoxService
+    where
+        S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync + 'static,
+    {
+        Self {
+            inner: Arc::new(http_service),
+        }
+    }
+}
+
+impl std::fmt::Debug for BoxService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BoxService").finish()
+    }
+}
+
+#[async_trait]
+impl Service<http::Request<Vec<u8>>> for BoxService {
+    type Response = http::Response<Vec<u8>>;
+    type Error = anyhow::Error;
+
+    async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
+        self.inner.call(http_request).await
+    }
+}
+
+
+/// A request and the response to it, as stored in a cassette file.
+/// Access tokens and passwords are redacted.
+#[derive(Clone, Debug, serde::Deserialize, Eq, PartialEq, serde::Serialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, Eq, PartialEq, serde::Serialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub uri: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, Eq, PartialEq, serde::Serialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+/// Records all requests and responses of the wrapped http service to a cassette file,
+/// which can be served by a `ReplayService` afterwards.
+/// Requests which fail without a response, e.g. due to connection errors, are not recorded.
+#[derive(Clone, Debug)]
+pub struct RecordingService<S> {
+    inner: S,
+    path: PathBuf,
+    interactions: Arc<Mutex<Vec<Interaction>>>,
+}
+impl<S> RecordingService<S> {
+    pub fn new(inner: S, path: PathBuf) -> RecordingService<S> {
+        Self {
+            inner,
+            path,
+            interactions: Default::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Service<http::Request<Vec<u8>>> for RecordingService<S>
+where
+    S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync,
+{
+    type Response = http::Response<Vec<u8>>;
+    type Error = anyhow::Error;
+
+    async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
+        let request = RecordedRequest {
+            method: http_request.method().to_string(),
+            uri: redact_uri(http_request.uri()),
+            headers: redact_headers(http_request.headers()),
+            body: redact_body(http_request.body()),
+        };
+
+        let http_response = self.inner.call(http_request).await?;
+
+        let response = RecordedResponse {
+            status: http_response.status().as_u16(),
+            headers: redact_headers(http_response.headers()),
+            body: redact_body(http_response.body()),
+        };
+
+        // the whole cassette is written after each interaction,
+        // so that it is complete even if the process is aborted afterwards
+        let serialized = {
+            let mut interactions = self.interactions.lock().unwrap();
+            interactions.push(Interaction { request, response });
+            ron::ser::to_string_pretty(&*interactions, ron::ser::PrettyConfig::default())?
+        };
+        std::fs::write(&self.path, serialized)?;
+
+        Ok(http_response)
+    }
+}
+
+/// Serves the responses of a cassette file recorded by a `RecordingService`, in the recorded order.
+/// Fails requests whose method or uri differ from the recorded one, or which exceed the recording.
+#[derive(Clone, Debug)]
+pub struct ReplayService {
+    interactions: Arc<Mutex<VecDeque<Interaction>>>,
+}
+impl ReplayService {
+    pub fn new(interactions: Vec<Interaction>) -> ReplayService {
+        Self {
+            interactions: Arc::new(Mutex::new(interactions.into())),
+        }
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<ReplayService, anyhow::Error> {
+        let file = std::fs::File::open(path)?;
+        let reader = std::io::BufReader::new(file);
+        let interactions = ron::de::from_reader(reader)?;
+        Ok(Self::new(interactions))
+    }
+}
+
+#[async_trait]
+impl Service<http::Request<Vec<u8>>> for ReplayService {
+    type Response = http::Response<Vec<u8>>;
+    type Error = anyhow::Error;
+
+    async fn call(&self, http_request: http::Request<Vec<u8>>) -> Result<http::Response<Vec<u8>>, anyhow::Error> {
+        let method = http_request.method().to_string();
+        let uri = redact_uri(http_request.uri());
+
+        let interaction = self.interactions.lock().unwrap().pop_front()
+            .ok_or_else(|| anyhow::anyhow!("no recorded interaction left for {} {}", method, uri))?;
+        if interaction.request.method != method || interaction.request.uri != uri {
+            anyhow::bail!(
+                "expected recorded request {} {}, got {} {}",
+                interaction.request.method, interaction.request.uri, method, uri,
+            );
+        }
+
+        let mut http_response = http::Response::new(interaction.response.body.into_bytes());
+        *http_response.status_mut() = http::StatusCode::from_u16(interaction.response.status)?;
+        for (name, value) in interaction.response.headers {
+            http_response.headers_mut().append(
+                http::header::HeaderName::try_from(name.as_str())?,
+                http::header::HeaderValue::try_from(value.as_str())?,
+            );
+        }
+        Ok(http_response)
+    }
+}
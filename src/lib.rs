@@ -104,7 +104,7 @@ pub struct Session {
     pub user_id: String,
     /// The access token used for this session.
     pub access_token: String,
-    /// The ID of the client device
+    /// The ID of the client device, empty if unknown, e.g. for imported access tokens
     pub device_id: String,
 }
 
@@ -160,6 +160,71 @@ where
 
 
 
+/// Builds a session for an access token which was obtained elsewhere,
+/// after checking with the whoami endpoint that the token is valid.
+pub async fn session_from_access_token<S>(http_service: S, base_url: String, access_token: String)
+    -> Result<Session, MatrixLibError<ruma::api::client::Error>>
+where
+    S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync,
+{
+    let service = MatrixService::new(http_service, base_url.clone(), access_token.clone());
+    let response = service.call(ruma::api::client::r0::account::whoami::Request::new()).await?;
+
+    Ok(Session {
+        base_url,
+        user_id: response.user_id.to_string(),
+        access_token,
+        // whoami does not tell the device
+        device_id: String::new(),
+    })
+}
+
+
+/// Hex-encoded HMAC-SHA1 over the registration details, keyed with the registration_shared_secret of synapse.
+pub fn registration_mac(shared_secret: &str, nonce: &str, username: &str, password: &str, admin: bool, user_type: Option<&str>) -> String {
+    use hmac::{Mac, NewMac};
+
+    let mut mac = hmac::Hmac::<sha1::Sha1>::new_varkey(shared_secret.as_bytes())
+        .expect("HMAC can take keys of any size");
+    mac.update(nonce.as_bytes());
+    mac.update(b"\x00");
+    mac.update(username.as_bytes());
+    mac.update(b"\x00");
+    mac.update(password.as_bytes());
+    mac.update(b"\x00");
+    mac.update(if admin { b"admin" as &[u8] } else { b"notadmin" });
+    if let Some(user_type) = user_type {
+        mac.update(b"\x00");
+        mac.update(user_type.as_bytes());
+    }
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Registers a new user with the shared secret from the synapse configuration,
+/// which works on servers without any user yet, and without logging in.
+pub async fn register_with_shared_secret<S>(
+    http_service: S,
+    base_url: String,
+    shared_secret: &str,
+    username: String,
+    password: String,
+    admin: bool,
+    displayname: Option<String>,
+) -> Result<register::Response, MatrixLibError<ruma::api::client::Error>>
+where
+    S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Send + Sync,
+{
+    let service = AnonymousMatrixService::new(http_service, base_url);
+    let nonce = service.call(register_nonce::Request::new()).await?.nonce;
+
+    let mac = registration_mac(shared_secret, &nonce, &username, &password, admin, None);
+    let mut request = register::Request::new(nonce, username, password, admin, mac);
+    request.displayname = displayname;
+    service.call(request).await
+}
+
+
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum AutoDiscoveryError {
     /// Retrieve the specific piece of information from the user in a way which fits within the
@@ -183,6 +248,17 @@ pub enum AutoDiscoveryError {
     FailError(String),
 }
 
+/// Checks that a discovered base_url is an absolute http(s) URL, before any request is sent to it
+fn validate_base_url(base_url: &str) -> Result<String, AutoDiscoveryError> {
+    let uri: http::Uri = base_url.parse()
+        .map_err(|error| AutoDiscoveryError::FailError(format!("invalid base_url {:?}: {}", base_url, error)))?;
+    let is_http = uri.scheme_str() == Some("http") || uri.scheme_str() == Some("https");
+    if !is_http || uri.authority().is_none() {
+        return Err(AutoDiscoveryError::FailError(format!("invalid base_url {:?}: not an http(s) URL", base_url)));
+    }
+    Ok(base_url.to_string())
+}
+
 pub async fn server_discovery<S>(http_service: S, user_id: String) -> Result<ruma::api::client::r0::session::login::DiscoveryInfo, AutoDiscoveryError>
     where S: Service<http::Request<Vec<u8>>, Response=http::Response<Vec<u8>>, Error=anyhow::Error> + Clone + Send + Sync
 {
@@ -265,8 +341,7 @@ pub async fn server_discovery<S>(http_service: S, user_id: String) -> Result<rum
     //     This value is to be used as the base URL of the homeserver.
     // 3e. Validate the homeserver base URL:
     // 3ei. Parse it as a URL. If it is not a URL, then FAIL_ERROR.
-    let base_url = discovery_info.homeserver.base_url.parse()
-        .map_err(|error| AutoDiscoveryError::FailError(format!("{}", error)))?;
+    let base_url = validate_base_url(&discovery_info.homeserver.base_url)?;
     let service = AnonymousMatrixService::new(http_service.clone(), base_url);
     // 3eii. Clients SHOULD validate that the URL points to a valid homeserver before accepting it
     //     by connecting to the /_matrix/client/versions endpoint,
@@ -285,8 +360,7 @@ pub async fn server_discovery<S>(http_service: S, user_id: String) -> Result<rum
     // m.identity_server property is present, but does not have a base_url value, then
     // FAIL_ERROR.
     if let Some(identity_server_info) = &discovery_info.identity_server {
-        let base_url = identity_server_info.base_url.parse()
-            .map_err(|error| AutoDiscoveryError::FailError(format!("{}", error)))?;
+        let base_url = validate_base_url(&identity_server_info.base_url)?;
         let service = AnonymousMatrixService::new(http_service.clone(), base_url);
         let identity_status_response = service.call(identity_status::Request).await;
 
@@ -300,30 +374,1409 @@ pub async fn server_discovery<S>(http_service: S, user_id: String) -> Result<rum
 }
 
 
-// TODO: try out a Paging API
+/// An endpoint request whose responses are split into pages of items.
+pub trait Paginated: ruma::api::OutgoingRequest + Clone {
+    type Item;
+
+    /// Splits a response to this request into its items
+    /// and the request for the following page, if there is one.
+    fn next_page(&self, response: Self::IncomingResponse) -> (Vec<Self::Item>, Option<Self>);
+}
+
+/// Lazily requests all pages of a paginated endpoint, starting with the given request,
+/// and yields the items of all pages one by one.
+/// The next page is only requested once all items of the previous page have been consumed.
+pub fn paginate<'a, S, Request>(service: &'a S, request: Request)
+    -> impl futures::Stream<Item = Result<Request::Item, S::Error>> + 'a
+where
+    S: Service<Request, Response = Request::IncomingResponse> + Sync,
+    Request: Paginated + Send + 'a,
+    Request::Item: 'a,
+    S::Error: 'a,
+{
+    use futures::stream::{self, TryStreamExt};
+
+    stream::try_unfold(Some(request), move |request| async move {
+        let request = match request {
+            Some(request) => request,
+            None => return Ok(None),
+        };
+        let response = service.call(request.clone()).await?;
+        let (items, next_request) = request.next_page(response);
+        let items = stream::iter(items.into_iter().map(Ok::<_, S::Error>));
+        Ok::<_, S::Error>(Some((items, next_request)))
+    }).try_flatten()
+}
 
 
 #[cfg(test)]
 mod tests {
-    use super::Service;
+    use super::{Service, MatrixLibError, AutoDiscoveryError};
+    use super::http_services::{MockService, Expectation};
+    use futures::TryStreamExt;
+    use http::{Method, StatusCode};
+    use serde_json::json;
+    use std::convert::TryFrom;
+
+    const BASE_URL: &str = "https://matrix.example.org";
+
+    fn matrix_service(mock: &MockService) -> super::MatrixService<MockService> {
+        super::MatrixService::new(mock.clone(), BASE_URL.to_string(), "access_token".to_string())
+    }
 
-    // TODO: move to unit test
-    async fn test_version_service() {
-        let server_uri = http::Uri::from_static("https://ayuthay.wolkenplanet.de");
+    fn user_id() -> ruma::UserId {
+        ruma::UserId::try_from("@alice:example.org").unwrap()
+    }
 
-        let service = super::AnonymousMatrixService::new(super::http_services::ReqwestService::new(), server_uri.clone());
+    fn room_id() -> ruma::RoomId {
+        ruma::RoomId::try_from("!room:example.org").unwrap()
+    }
 
-        // TODO: VersionRequest runs into an infinite recursion loop when /_synapse is not yet activated in nginx
-        let version_request = super::VersionRequest;
-        let version_response = service.call(version_request).await.unwrap();
-        println!("{:?}", version_response);
+    fn user_details(name: &str) -> serde_json::Value {
+        json!({
+            "name": name,
+            "password_hash": null,
+            "is_guest": 0,
+            "admin": 0,
+            "user_type": null,
+            "deactivated": 0,
+            "displayname": "Alice",
+            "avatar_url": null,
+        })
+    }
+
+    fn room_details(room_id: &str) -> serde_json::Value {
+        json!({
+            "room_id": room_id,
+            "name": "Room",
+            "canonical_alias": "#room:example.org",
+            "joined_members": 2,
+            "joined_local_members": 1,
+            "version": "5",
+            "creator": "@alice:example.org",
+            "encryption": null,
+            "federatable": true,
+            "public": false,
+            "join_rules": "invite",
+            "guest_access": null,
+            "history_visibility": "shared",
+            "state_events": 12,
+        })
+    }
+
+    #[test]
+    fn version() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/server_version")
+            .respond_json(StatusCode::OK, json!({ "server_version": "1.19.1", "python_version": "3.8.5" })));
+
+        let response = smol::run(matrix_service(&mock).call(super::version::Request::new())).unwrap();
+        assert_eq!(response.server_version, "1.19.1");
+        assert_eq!(response.python_version, "3.8.5");
     }
 
     #[test]
-    fn run() {
-        smol::run(async {
-            test_version_service().await
+    fn identity_status() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://identity.example.org/_matrix/identity/api/v1")
+            .respond_json(StatusCode::OK, json!({})));
+
+        let service = super::AnonymousMatrixService::new(mock, "https://identity.example.org".to_string());
+        smol::run(service.call(super::identity_status::Request)).unwrap();
+    }
+
+    #[test]
+    fn list_accounts() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users?from=0&limit=2&guests=false")
+            .respond_json(StatusCode::OK, json!({
+                "users": [user_details("@alice:example.org"), user_details("@bob:example.org")],
+                "next_token": "2",
+                "total": 3,
+            })));
+
+        let request = assign::assign!(super::list_accounts::Request::new(), {
+            from: Some("0".to_string()),
+            limit: Some(js_int::UInt::from(2u32)),
+            guests: Some(false),
         });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.users.len(), 2);
+        assert_eq!(response.users[1].name.as_str(), "@bob:example.org");
+        assert_eq!(response.next_token.as_deref(), Some("2"));
+        assert_eq!(response.total, js_int::UInt::from(3u32));
     }
-}
 
+    #[test]
+    fn bool_or_int() {
+        let mut user = user_details("@alice:example.org");
+        let details: super::list_accounts::UserDetails = serde_json::from_value(user.clone()).unwrap();
+        assert!(!details.admin && !details.is_guest && !details.deactivated);
+
+        user["admin"] = json!(1);
+        user["deactivated"] = json!(true);
+        user["is_guest"] = json!(false);
+        let details: super::list_accounts::UserDetails = serde_json::from_value(user.clone()).unwrap();
+        assert!(details.admin && details.deactivated && !details.is_guest);
+        // serialized as bools again
+        assert_eq!(serde_json::to_value(&details).unwrap()["admin"], json!(true));
+
+        user["admin"] = json!(2);
+        assert!(serde_json::from_value::<super::list_accounts::UserDetails>(user.clone()).is_err());
+        user["admin"] = json!("true");
+        assert!(serde_json::from_value::<super::list_accounts::UserDetails>(user).is_err());
+    }
+
+    #[test]
+    fn query_user_with_bools() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org")
+            .respond_json(StatusCode::OK, json!({
+                "displayname": "Alice",
+                "threepids": [],
+                "avatar_url": null,
+                "admin": false,
+                "deactivated": true,
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::query_user::Request::new(user_id()))).unwrap();
+        assert!(!response.admin);
+        assert!(response.deactivated);
+    }
+
+    #[test]
+    fn paginate_list_accounts() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users?limit=2")
+            .respond_json(StatusCode::OK, json!({
+                "users": [user_details("@alice:example.org"), user_details("@bob:example.org")],
+                "next_token": "2",
+                "total": 3,
+            })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users?from=2&limit=2")
+            .respond_json(StatusCode::OK, json!({
+                "users": [user_details("@carol:example.org")],
+                "total": 3,
+            })));
+
+        let service = matrix_service(&mock);
+        let request = assign::assign!(super::list_accounts::Request::new(), {
+            limit: Some(js_int::UInt::from(2u32)),
+        });
+        let users: Vec<_> = smol::run(super::paginate(&service, request).try_collect()).unwrap();
+        let names: Vec<_> = users.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(names, vec!["@alice:example.org", "@bob:example.org", "@carol:example.org"]);
+    }
+
+    #[test]
+    fn paginate_list_accounts_opaque_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users")
+            .respond_json(StatusCode::OK, json!({
+                "users": [user_details("@alice:example.org")],
+                "next_token": "s2_alice",
+                "total": 2,
+            })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users?from=s2_alice")
+            .respond_json(StatusCode::OK, json!({
+                "users": [user_details("@bob:example.org")],
+                "total": 2,
+            })));
+
+        // a token which is not an offset must not end the listing early
+        let service = matrix_service(&mock);
+        let users: Vec<_> = smol::run(super::paginate(&service, super::list_accounts::Request::new()).try_collect()).unwrap();
+        assert_eq!(users.len(), 2);
+    }
+
+    #[test]
+    fn paginate_list_rooms() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms")
+            .respond_json(StatusCode::OK, json!({
+                "rooms": [room_details("!a:example.org")],
+                "offset": 0,
+                "total_rooms": 2,
+                "next_batch": 1,
+            })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms?from=1")
+            .respond_json(StatusCode::OK, json!({
+                "rooms": [room_details("!b:example.org")],
+                "offset": 1,
+                "total_rooms": 2,
+                "prev_batch": 0,
+            })));
+
+        let service = matrix_service(&mock);
+        let rooms: Vec<_> = smol::run(super::paginate(&service, super::list_rooms::Request::new()).try_collect()).unwrap();
+        let room_ids: Vec<_> = rooms.iter().map(|room| room.room_id.as_str()).collect();
+        assert_eq!(room_ids, vec!["!a:example.org", "!b:example.org"]);
+        assert_eq!(rooms[0].canonical_alias.as_ref().map(|alias| alias.as_str()), Some("#room:example.org"));
+    }
+
+    #[test]
+    fn list_rooms() {
+        let mock = MockService::new();
+        let mut room = room_details("!a:example.org");
+        room["encryption"] = json!("m.megolm.v1.aes-sha2");
+        room["guest_access"] = json!("can_join");
+        room["join_rules"] = json!("knock_restricted_v2");
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms?order_by=joined_members&dir=b&search_term=Room")
+            .respond_json(StatusCode::OK, json!({
+                "rooms": [room],
+                "offset": 0,
+                "total_rooms": 1,
+            })));
+
+        let request = assign::assign!(super::list_rooms::Request::new(), {
+            order_by: Some(super::list_rooms::RoomOrderBy::JoinedMembers),
+            dir: Some(super::Direction::Backward),
+            search_term: Some("Room".to_string()),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        let room = &response.rooms[0];
+        assert_eq!(room.encryption, Some(super::list_rooms::Encryption::MegolmV1AesSha2));
+        assert_eq!(room.guest_access, Some(super::list_rooms::GuestAccess::CanJoin));
+        assert_eq!(room.history_visibility, Some(super::list_rooms::HistoryVisibility::Shared));
+        // unknown values are kept, and serialized as they were received
+        assert_eq!(room.join_rules, Some(super::list_rooms::JoinRule::Other("knock_restricted_v2".to_string())));
+        assert_eq!(serde_json::to_value(room).unwrap()["join_rules"], "knock_restricted_v2");
+    }
+
+    #[test]
+    fn string_enum_from_str() {
+        use super::list_rooms::RoomOrderBy;
+
+        assert_eq!("state_events".parse::<RoomOrderBy>(), Ok(RoomOrderBy::StateEvents));
+        assert_eq!(RoomOrderBy::StateEvents.to_string(), "state_events");
+        // only known values are accepted as input
+        assert!("size".parse::<RoomOrderBy>().is_err());
+        assert_eq!(RoomOrderBy::from("size".to_string()), RoomOrderBy::Other("size".to_string()));
+        assert!(super::Direction::VARIANTS.iter().all(|value| value.parse::<super::Direction>().is_ok()));
+    }
+
+    #[test]
+    fn paginate_error() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms")
+            .respond_json(StatusCode::OK, json!({
+                "rooms": [room_details("!a:example.org")],
+                "offset": 0,
+                "total_rooms": 2,
+                "next_batch": 1,
+            })));
+
+        let service = matrix_service(&mock);
+        let rooms: Result<Vec<_>, _> = smol::run(super::paginate(&service, super::list_rooms::Request::new()).try_collect());
+        assert!(matches!(rooms, Err(MatrixLibError::HttpService(_))));
+    }
+
+    #[test]
+    fn query_user() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org")
+            .respond_json(StatusCode::OK, json!({
+                "displayname": "Alice",
+                "threepids": [{ "medium": "email", "address": "alice@example.org" }],
+                "avatar_url": null,
+                "admin": 1,
+                "deactivated": 0,
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::query_user::Request::new(user_id()))).unwrap();
+        assert_eq!(response.displayname.as_deref(), Some("Alice"));
+        assert_eq!(response.threepids.unwrap()[0].address, "alice@example.org");
+        assert!(response.admin);
+        assert!(!response.deactivated);
+    }
+
+    #[test]
+    fn unknown_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org")
+            .respond_json(StatusCode::UNAUTHORIZED, json!({
+                "errcode": "M_UNKNOWN_TOKEN",
+                "error": "Invalid macaroon passed.",
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::query_user::Request::new(user_id())));
+        match response {
+            Err(MatrixLibError::FromHttpResponseError(ruma::api::error::FromHttpResponseError::Http(
+                ruma::api::error::ServerError::Known(error)))) =>
+                assert_eq!(error.status_code, StatusCode::UNAUTHORIZED),
+            other => panic!("expected known server error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn list_joined_rooms() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/users/@alice:example.org/joined_rooms")
+            .respond_json(StatusCode::OK, json!({
+                "joined_rooms": ["!room:example.org"],
+                "total": 1,
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::list_joined_rooms::Request::new(user_id()))).unwrap();
+        assert_eq!(response.joined_rooms, vec![room_id()]);
+        assert_eq!(response.total, js_int::UInt::from(1u32));
+    }
+
+    #[test]
+    fn purge_room() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/purge_room")
+            .body(json!({ "room_id": "!room:example.org" }))
+            .respond_json(StatusCode::OK, json!({})));
+
+        smol::run(matrix_service(&mock).call(super::purge_room::Request::new(room_id()))).unwrap();
+    }
+
+    #[test]
+    fn create_modify_account() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::PUT, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org")
+            .body(json!({ "password": "hunter2", "displayname": "Alice", "admin": false }))
+            .respond_json(StatusCode::CREATED, json!({
+                "name": "@alice:example.org",
+                "password_hash": "$2b$12$hash",
+                "is_guest": 0,
+                "admin": 0,
+                "consent_version": null,
+                "consent_server_notice_sent": null,
+                "appservice_id": null,
+                "creation_ts": 1597838373,
+                "user_type": null,
+                "deactivated": 0,
+                "displayname": "Alice",
+                "avatar_url": null,
+                "threepids": [],
+            })));
+
+        let request = assign::assign!(super::create_modify_account::Request::new(user_id(), "hunter2".to_string()), {
+            displayname: Some("Alice".to_string()),
+            admin: Some(false),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.name, user_id());
+        assert_eq!(response.creation_ts, js_int::UInt::from(1597838373u32));
+        assert!(!response.admin);
+    }
+
+    #[test]
+    fn reset_password() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/reset_password/@alice:example.org")
+            .body(json!({ "new_password": "hunter2", "logout_devices": false }))
+            .respond_json(StatusCode::OK, json!({})));
+
+        let request = super::reset_password::Request::new(user_id(), "hunter2".to_string(), Some(false));
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn user_is_admin() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/users/@alice:example.org/admin")
+            .respond_json(StatusCode::OK, json!({ "admin": true })));
+
+        let response = smol::run(matrix_service(&mock).call(super::user_is_admin::Request::new(user_id()))).unwrap();
+        assert!(response.admin);
+    }
+
+    #[test]
+    fn delete_room() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::DELETE, "https://matrix.example.org/_synapse/admin/v2/rooms/!room:example.org")
+            .body(json!({ "block": true, "purge": true }))
+            .respond_json(StatusCode::OK, json!({ "delete_id": "abc" })));
+
+        let request = assign::assign!(super::delete_room::Request::new(room_id()), {
+            block: Some(true),
+            purge: Some(true),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.delete_id, "abc");
+    }
+
+    #[test]
+    fn delete_status() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/rooms/delete_status/abc")
+            .respond_json(StatusCode::OK, json!({
+                "status": "complete",
+                "shutdown_room": {
+                    "kicked_users": ["@alice:example.org"],
+                    "failed_to_kick_users": [],
+                    "local_aliases": ["#room:example.org"],
+                    "new_room_id": null,
+                },
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::delete_status::Request::new("abc".to_string()))).unwrap();
+        assert_eq!(response.status, super::delete_room::Status::Complete);
+        assert!(response.status.is_finished());
+        assert_eq!(response.shutdown_room.kicked_users, vec![user_id()]);
+    }
+
+    #[test]
+    fn room_delete_status() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/rooms/!room:example.org/delete_status")
+            .respond_json(StatusCode::OK, json!({
+                "results": [
+                    { "delete_id": "abc", "status": "purging" },
+                    { "delete_id": "def", "status": "failed", "error": "oops" },
+                ],
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::room_delete_status::Request::new(room_id()))).unwrap();
+        assert_eq!(response.results.len(), 2);
+        assert!(!response.results[0].status.is_finished());
+        assert_eq!(response.results[1].error.as_deref(), Some("oops"));
+    }
+
+    fn registration_token(token: &str) -> serde_json::Value {
+        json!({
+            "token": token,
+            "uses_allowed": 3,
+            "pending": 1,
+            "completed": 1,
+            "expiry_time": null,
+        })
+    }
+
+    #[test]
+    fn list_registration_tokens() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/registration_tokens?valid=true")
+            .respond_json(StatusCode::OK, json!({
+                "registration_tokens": [registration_token("abcd"), registration_token("efgh")],
+            })));
+
+        let request = assign::assign!(super::list_registration_tokens::Request::new(), {
+            valid: Some(true),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.registration_tokens.len(), 2);
+        assert_eq!(response.registration_tokens[0].remaining_uses(), Some(js_int::UInt::from(1u32)));
+    }
+
+    #[test]
+    fn get_registration_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/registration_tokens/abcd")
+            .respond_json(StatusCode::OK, registration_token("abcd")));
+
+        let response = smol::run(matrix_service(&mock).call(super::get_registration_token::Request::new("abcd".to_string()))).unwrap();
+        assert_eq!(response.registration_token.token, "abcd");
+        assert_eq!(response.registration_token.expiry_time, None);
+    }
+
+    #[test]
+    fn create_registration_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/registration_tokens/new")
+            .body(json!({ "uses_allowed": 3, "length": 4 }))
+            .respond_json(StatusCode::OK, registration_token("abcd")));
+
+        let request = assign::assign!(super::create_registration_token::Request::new(), {
+            uses_allowed: Some(js_int::UInt::from(3u32)),
+            length: Some(js_int::UInt::from(4u32)),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.registration_token.uses_allowed, Some(js_int::UInt::from(3u32)));
+    }
+
+    #[test]
+    fn update_registration_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::PUT, "https://matrix.example.org/_synapse/admin/v1/registration_tokens/abcd")
+            .body(json!({ "uses_allowed": null, "expiry_time": 1625394937000u64 }))
+            .respond_json(StatusCode::OK, registration_token("abcd")));
+
+        let request = assign::assign!(super::update_registration_token::Request::new("abcd".to_string()), {
+            uses_allowed: Some(None),
+            expiry_time: Some(Some(js_int::UInt::try_from(1625394937000u64).unwrap())),
+        });
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn delete_registration_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::DELETE, "https://matrix.example.org/_synapse/admin/v1/registration_tokens/abcd")
+            .respond_json(StatusCode::OK, json!({})));
+
+        smol::run(matrix_service(&mock).call(super::delete_registration_token::Request::new("abcd".to_string()))).unwrap();
+    }
+
+    #[test]
+    fn list_devices() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org/devices")
+            .respond_json(StatusCode::OK, json!({
+                "devices": [
+                    {
+                        "device_id": "QBUAZIFURK",
+                        "display_name": "android",
+                        "last_seen_ip": "1.2.3.4",
+                        "last_seen_ts": 1474491775024u64,
+                        "user_id": "@alice:example.org",
+                    },
+                    {
+                        "device_id": "AUIECTSRND",
+                        "display_name": null,
+                        "last_seen_ip": null,
+                        "last_seen_ts": null,
+                        "user_id": "@alice:example.org",
+                    },
+                ],
+                "total": 2,
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::list_devices::Request::new(user_id()))).unwrap();
+        assert_eq!(response.total, js_int::UInt::from(2u32));
+        assert_eq!(response.devices[0].last_seen_ip.as_deref(), Some("1.2.3.4"));
+        assert_eq!(response.devices[1].last_seen_ts, None);
+    }
+
+    #[test]
+    fn get_device() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org/devices/QBUAZIFURK")
+            .respond_json(StatusCode::OK, json!({
+                "device_id": "QBUAZIFURK",
+                "display_name": "android",
+                "last_seen_ip": "1.2.3.4",
+                "last_seen_ts": 1474491775024u64,
+                "user_id": "@alice:example.org",
+            })));
+
+        let request = super::get_device::Request::new(user_id(), "QBUAZIFURK".to_string());
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.device.display_name.as_deref(), Some("android"));
+    }
+
+    #[test]
+    fn update_device() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::PUT, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org/devices/QBUAZIFURK")
+            .body(json!({ "display_name": "phone" }))
+            .respond_json(StatusCode::OK, json!({})));
+
+        let request = super::update_device::Request::new(user_id(), "QBUAZIFURK".to_string(), "phone".to_string());
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn delete_device() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::DELETE, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org/devices/QBUAZIFURK")
+            .respond_json(StatusCode::OK, json!({})));
+
+        let request = super::delete_device::Request::new(user_id(), "QBUAZIFURK".to_string());
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn delete_devices() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v2/users/@alice:example.org/delete_devices")
+            .body(json!({ "devices": ["QBUAZIFURK", "AUIECTSRND"] }))
+            .respond_json(StatusCode::OK, json!({})));
+
+        let request = super::delete_devices::Request::new(user_id(), vec!["QBUAZIFURK".to_string(), "AUIECTSRND".to_string()]);
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn deactivate_account() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/deactivate/@alice:example.org")
+            .body(json!({ "erase": true }))
+            .respond_json(StatusCode::OK, json!({ "id_server_unbind_result": "success" })));
+
+        let request = super::deactivate_account::Request::new(user_id(), Some(true));
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.id_server_unbind_result, "success");
+    }
+
+    #[test]
+    fn room_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/room/!room:example.org/media")
+            .respond_json(StatusCode::OK, json!({
+                "local": ["mxc://example.org/xwvutsrqponmlkjihgfedcba"],
+                "remote": ["mxc://matrix.org/abcdefghijklmnopqrstuvwx"],
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::room_media::Request::new(room_id()))).unwrap();
+        assert_eq!(response.local, vec!["mxc://example.org/xwvutsrqponmlkjihgfedcba"]);
+        assert_eq!(response.remote, vec!["mxc://matrix.org/abcdefghijklmnopqrstuvwx"]);
+    }
+
+    fn media(media_id: &str) -> serde_json::Value {
+        json!({
+            "created_ts": 100400,
+            "last_access_ts": null,
+            "media_id": media_id,
+            "media_length": 67,
+            "media_type": "image/png",
+            "quarantined_by": null,
+            "safe_from_quarantine": false,
+            "upload_name": "test1.png",
+        })
+    }
+
+    #[test]
+    fn paginate_list_user_media() {
+        let mock = MockService::new();
+        // synapse on sqlite returns flags as integers
+        let mut protected_media = media("BCDEFGHIJKLMNOPQRSTUVWXY");
+        protected_media["safe_from_quarantine"] = json!(1);
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/users/@alice:example.org/media?limit=1")
+            .respond_json(StatusCode::OK, json!({
+                "media": [media("ABCDEFGHIJKLMNOPQRSTUVWX")],
+                "next_token": 1,
+                "total": 2,
+            })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/users/@alice:example.org/media?from=1&limit=1")
+            .respond_json(StatusCode::OK, json!({
+                "media": [protected_media],
+                "total": 2,
+            })));
+
+        let service = matrix_service(&mock);
+        let request = assign::assign!(super::list_user_media::Request::new(user_id()), {
+            limit: Some(js_int::UInt::from(1u32)),
+        });
+        let media: Vec<_> = smol::run(super::paginate(&service, request).try_collect()).unwrap();
+        let media_ids: Vec<_> = media.iter().map(|media| media.media_id.as_str()).collect();
+        assert_eq!(media_ids, vec!["ABCDEFGHIJKLMNOPQRSTUVWX", "BCDEFGHIJKLMNOPQRSTUVWXY"]);
+        assert_eq!(media[0].media_length, js_int::UInt::from(67u32));
+        assert!(!media[0].safe_from_quarantine);
+        assert!(media[1].safe_from_quarantine);
+    }
+
+    #[test]
+    fn delete_user_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::DELETE, "https://matrix.example.org/_synapse/admin/v1/users/@alice:example.org/media")
+            .respond_json(StatusCode::OK, json!({
+                "deleted_media": ["ABCDEFGHIJKLMNOPQRSTUVWX"],
+                "total": 1,
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::delete_user_media::Request::new(user_id()))).unwrap();
+        assert_eq!(response.deleted_media, vec!["ABCDEFGHIJKLMNOPQRSTUVWX"]);
+    }
+
+    #[test]
+    fn delete_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::DELETE, "https://matrix.example.org/_synapse/admin/v1/media/example.org/ABCDEFGHIJKLMNOPQRSTUVWX")
+            .respond_json(StatusCode::OK, json!({
+                "deleted_media": ["ABCDEFGHIJKLMNOPQRSTUVWX"],
+                "total": 1,
+            })));
+
+        let request = super::delete_media::Request::new("example.org".to_string(), "ABCDEFGHIJKLMNOPQRSTUVWX".to_string());
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.total, js_int::UInt::from(1u32));
+    }
+
+    #[test]
+    fn delete_local_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/media/example.org/delete?before_ts=1600000000000&size_gt=1024&keep_profiles=false")
+            .respond_json(StatusCode::OK, json!({
+                "deleted_media": ["ABCDEFGHIJKLMNOPQRSTUVWX", "BCDEFGHIJKLMNOPQRSTUVWXY"],
+                "total": 2,
+            })));
+
+        let request = assign::assign!(super::delete_local_media::Request::new(
+            "example.org".to_string(), js_int::UInt::try_from(1600000000000u64).unwrap()), {
+            size_gt: Some(js_int::UInt::from(1024u32)),
+            keep_profiles: Some(false),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.deleted_media.len(), 2);
+    }
+
+    #[test]
+    fn purge_media_cache() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/purge_media_cache?before_ts=1600000000000")
+            .respond_json(StatusCode::OK, json!({ "deleted": 10 })));
+
+        let request = super::purge_media_cache::Request::new(js_int::UInt::try_from(1600000000000u64).unwrap());
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.deleted, js_int::UInt::from(10u32));
+    }
+
+    #[test]
+    fn quarantine_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/media/quarantine/example.org/ABCDEFGHIJKLMNOPQRSTUVWX")
+            .respond_json(StatusCode::OK, json!({})));
+
+        let request = super::quarantine_media::Request::new("example.org".to_string(), "ABCDEFGHIJKLMNOPQRSTUVWX".to_string());
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn unquarantine_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/media/unquarantine/example.org/ABCDEFGHIJKLMNOPQRSTUVWX")
+            .respond_json(StatusCode::OK, json!({})));
+
+        let request = super::unquarantine_media::Request::new("example.org".to_string(), "ABCDEFGHIJKLMNOPQRSTUVWX".to_string());
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn quarantine_room_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/room/!room:example.org/media/quarantine")
+            .respond_json(StatusCode::OK, json!({ "num_quarantined": 10 })));
+
+        let response = smol::run(matrix_service(&mock).call(super::quarantine_room_media::Request::new(room_id()))).unwrap();
+        assert_eq!(response.num_quarantined, js_int::UInt::from(10u32));
+    }
+
+    #[test]
+    fn quarantine_user_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/user/@alice:example.org/media/quarantine")
+            .respond_json(StatusCode::OK, json!({ "num_quarantined": 3 })));
+
+        let response = smol::run(matrix_service(&mock).call(super::quarantine_user_media::Request::new(user_id()))).unwrap();
+        assert_eq!(response.num_quarantined, js_int::UInt::from(3u32));
+    }
+
+    #[test]
+    fn protect_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/media/protect/ABCDEFGHIJKLMNOPQRSTUVWX")
+            .respond_json(StatusCode::OK, json!({})));
+
+        smol::run(matrix_service(&mock).call(super::protect_media::Request::new("ABCDEFGHIJKLMNOPQRSTUVWX".to_string()))).unwrap();
+    }
+
+    #[test]
+    fn unprotect_media() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/media/unprotect/ABCDEFGHIJKLMNOPQRSTUVWX")
+            .respond_json(StatusCode::OK, json!({})));
+
+        smol::run(matrix_service(&mock).call(super::unprotect_media::Request::new("ABCDEFGHIJKLMNOPQRSTUVWX".to_string()))).unwrap();
+    }
+
+    fn event_report_json(id: u32) -> serde_json::Value {
+        json!({
+            "id": id,
+            "received_ts": 1570897107409u64,
+            "user_id": "@bob:example.org",
+            "room_id": "!room:example.org",
+            "name": "Room",
+            "canonical_alias": null,
+            "event_id": "$event:example.org",
+            "sender": "@alice:example.org",
+            "score": -100,
+            "reason": "spam",
+        })
+    }
+
+    #[test]
+    fn paginate_event_reports() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/event_reports?limit=1&room_id=!room:example.org")
+            .respond_json(StatusCode::OK, json!({
+                "event_reports": [event_report_json(2)],
+                "next_token": 1,
+                "total": 2,
+            })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/event_reports?from=1&limit=1&room_id=!room:example.org")
+            .respond_json(StatusCode::OK, json!({
+                "event_reports": [event_report_json(1)],
+                "total": 2,
+            })));
+
+        let service = matrix_service(&mock);
+        let request = assign::assign!(super::event_reports::Request::new(), {
+            limit: Some(js_int::UInt::from(1u32)),
+            room_id: Some("!room:example.org".to_string()),
+        });
+        let reports: Vec<_> = smol::run(super::paginate(&service, request).try_collect()).unwrap();
+        let ids: Vec<_> = reports.iter().map(|report| report.id).collect();
+        assert_eq!(ids, vec![js_int::UInt::from(2u32), js_int::UInt::from(1u32)]);
+        assert_eq!(reports[0].score, Some(js_int::Int::from(-100)));
+        assert_eq!(reports[0].reason.as_deref(), Some("spam"));
+    }
+
+    #[test]
+    fn event_report() {
+        let mock = MockService::new();
+        let mut details = event_report_json(2);
+        details["event_json"] = json!({
+            "type": "m.room.message",
+            "content": { "body": "buy now", "msgtype": "m.text" },
+        });
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/event_reports/2")
+            .respond_json(StatusCode::OK, details));
+
+        let response = smol::run(matrix_service(&mock).call(super::event_report::Request::new(js_int::UInt::from(2u32)))).unwrap();
+        assert_eq!(response.details.report.sender.as_str(), "@alice:example.org");
+        assert_eq!(response.details.event_json["content"]["body"], "buy now");
+    }
+
+    #[test]
+    fn room_details() {
+        let mock = MockService::new();
+        let mut details = room_details("!room:example.org");
+        details["topic"] = json!("Chat about things");
+        details["avatar"] = json!("mxc://example.org/avatar");
+        details["room_type"] = json!(null);
+        details["joined_local_devices"] = json!(2);
+        // synapse on sqlite returns flags as integers
+        details["federatable"] = json!(1);
+        details["public"] = json!(0);
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms/!room:example.org")
+            .respond_json(StatusCode::OK, details));
+
+        let response = smol::run(matrix_service(&mock).call(super::room_details::Request::new(room_id()))).unwrap();
+        assert_eq!(response.room.room_id, room_id());
+        assert_eq!(response.room.topic.as_deref(), Some("Chat about things"));
+        assert_eq!(response.room.room_type, None);
+        assert_eq!(response.room.joined_local_devices, Some(js_int::UInt::from(2u32)));
+        assert!(response.room.federatable);
+        assert!(!response.room.public);
+    }
+
+    #[test]
+    fn room_members() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms/!room:example.org/members")
+            .respond_json(StatusCode::OK, json!({
+                "members": ["@alice:example.org", "@bob:matrix.org"],
+                "total": 2,
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::room_members::Request::new(room_id()))).unwrap();
+        assert_eq!(response.members[0], user_id());
+        assert_eq!(response.total, js_int::UInt::from(2u32));
+    }
+
+    #[test]
+    fn room_state() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms/!room:example.org/state")
+            .respond_json(StatusCode::OK, json!({
+                "state": [{
+                    "type": "m.room.create",
+                    "state_key": "",
+                    "sender": "@alice:example.org",
+                    "event_id": "$create:example.org",
+                    "room_id": "!room:example.org",
+                    "origin_server_ts": 1597838373000u64,
+                    "content": { "creator": "@alice:example.org", "room_version": "6" },
+                }],
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::room_state::Request::new(room_id()))).unwrap();
+        assert_eq!(response.state[0].event_type, "m.room.create");
+        assert_eq!(response.state[0].content["room_version"], "6");
+    }
+
+    #[test]
+    fn block_room() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::PUT, "https://matrix.example.org/_synapse/admin/v1/rooms/!room:example.org/block")
+            .body(json!({ "block": true }))
+            .respond_json(StatusCode::OK, json!({ "block": true })));
+
+        let response = smol::run(matrix_service(&mock).call(super::block_room::Request::new(room_id(), true))).unwrap();
+        assert!(response.block);
+    }
+
+    #[test]
+    fn room_block_status() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms/!room:example.org/block")
+            .respond_json(StatusCode::OK, json!({ "block": true, "user_id": "@alice:example.org" })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/rooms/!other:example.org/block")
+            .respond_json(StatusCode::OK, json!({ "block": false })));
+
+        let service = matrix_service(&mock);
+        let response = smol::run(service.call(super::room_block_status::Request::new(room_id()))).unwrap();
+        assert!(response.block);
+        assert_eq!(response.user_id, Some(user_id()));
+        let other_room_id = ruma::RoomId::try_from("!other:example.org").unwrap();
+        let response = smol::run(service.call(super::room_block_status::Request::new(other_room_id))).unwrap();
+        assert!(!response.block);
+        assert_eq!(response.user_id, None);
+    }
+
+    #[test]
+    fn make_room_admin() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/rooms/#room:example.org/make_room_admin")
+            .body(json!({ "user_id": "@alice:example.org" }))
+            .respond_json(StatusCode::OK, json!({})));
+
+        let room_alias = ruma::RoomIdOrAliasId::try_from("#room:example.org").unwrap();
+        let request = super::make_room_admin::Request::new(room_alias, Some(user_id()));
+        smol::run(matrix_service(&mock).call(request)).unwrap();
+    }
+
+    #[test]
+    fn join_room() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/join/#room:example.org")
+            .body(json!({ "user_id": "@alice:example.org" }))
+            .respond_json(StatusCode::OK, json!({ "room_id": "!room:example.org" })));
+
+        let room_alias = ruma::RoomIdOrAliasId::try_from("#room:example.org").unwrap();
+        let response = smol::run(matrix_service(&mock).call(super::join_room::Request::new(room_alias, user_id()))).unwrap();
+        assert_eq!(response.room_id, room_id());
+    }
+
+    #[test]
+    fn purge_history() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/purge_history/!room:example.org")
+            .body(json!({ "delete_local_events": false, "purge_up_to_ts": 1600000000000u64 }))
+            .respond_json(StatusCode::OK, json!({ "purge_id": "abc" })));
+
+        let request = assign::assign!(super::purge_history::Request::new(room_id()), {
+            delete_local_events: Some(false),
+            purge_up_to_ts: Some(js_int::UInt::try_from(1600000000000u64).unwrap()),
+        });
+        let response = smol::run(matrix_service(&mock).call(request)).unwrap();
+        assert_eq!(response.purge_id, "abc");
+    }
+
+    #[test]
+    fn purge_history_status() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/purge_history_status/abc")
+            .respond_json(StatusCode::OK, json!({ "status": "active" })));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/purge_history_status/def")
+            .respond_json(StatusCode::OK, json!({ "status": "failed", "error": "oops" })));
+
+        let service = matrix_service(&mock);
+        let response = smol::run(service.call(super::purge_history_status::Request::new("abc".to_string()))).unwrap();
+        assert_eq!(response.status, super::purge_history_status::Status::Active);
+        assert!(!response.status.is_finished());
+        let response = smol::run(service.call(super::purge_history_status::Request::new("def".to_string()))).unwrap();
+        assert!(response.status.is_finished());
+        assert_eq!(response.error.as_deref(), Some("oops"));
+    }
+
+    #[test]
+    fn unknown_statuses_are_not_finished() {
+        let status: super::delete_room::Status = serde_json::from_value(json!("waiting")).unwrap();
+        assert_eq!(status, super::delete_room::Status::Other("waiting".to_string()));
+        assert!(!status.is_finished());
+        let status: super::purge_history_status::Status = serde_json::from_value(json!("queued")).unwrap();
+        assert_eq!(status.as_str(), "queued");
+        assert!(!status.is_finished());
+    }
+
+    #[test]
+    fn registration_mac() {
+        // computed with python's hmac module, like synapse's register_new_matrix_user does
+        assert_eq!(
+            super::registration_mac("shared_secret", "nonce123", "alice", "hunter2", false, None),
+            "fa07619ad75fee1e5c70a5f20acd259f2f83f115",
+        );
+        assert_eq!(
+            super::registration_mac("shared_secret", "nonce123", "alice", "hunter2", true, None),
+            "2a3c9eb4e4a41db9b8052edcf324f7f368c3c30c",
+        );
+    }
+
+    #[test]
+    fn register_with_shared_secret() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/register")
+            .respond_json(StatusCode::OK, json!({ "nonce": "nonce123" })));
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/register")
+            .body(json!({
+                "nonce": "nonce123",
+                "username": "alice",
+                "password": "hunter2",
+                "admin": false,
+                "mac": "fa07619ad75fee1e5c70a5f20acd259f2f83f115",
+            }))
+            .respond_json(StatusCode::OK, json!({
+                "access_token": "access_token",
+                "user_id": "@alice:example.org",
+                "home_server": "example.org",
+                "device_id": "ABCDEFGHIJ",
+            })));
+
+        let response = smol::run(super::register_with_shared_secret(
+            mock, BASE_URL.to_string(), "shared_secret", "alice".to_string(), "hunter2".to_string(), false, None,
+        )).unwrap();
+        assert_eq!(response.user_id, user_id());
+        assert_eq!(response.access_token, "access_token");
+    }
+
+    #[test]
+    fn whois() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/whois/@alice:example.org")
+            .respond_json(StatusCode::OK, json!({
+                "user_id": "@alice:example.org",
+                "devices": {
+                    "QBUAZIFURK": {
+                        "sessions": [{
+                            "connections": [
+                                { "ip": "1.2.3.4", "last_seen": 1417222374433u64, "user_agent": "Element" },
+                                { "ip": "5.6.7.8", "last_seen": 1417222374500u64, "user_agent": null },
+                            ],
+                        }],
+                    },
+                    "AUIECTSRND": {
+                        "sessions": [{
+                            "connections": [
+                                { "ip": "1.2.3.4", "last_seen": 1417222370000u64, "user_agent": "curl" },
+                            ],
+                        }],
+                    },
+                },
+            })));
+
+        let response = smol::run(matrix_service(&mock).call(super::whois::Request::new(user_id()))).unwrap();
+        assert_eq!(response.user_id, user_id());
+        let connections: Vec<_> = response.connections()
+            .map(|(device_id, connection)| (device_id, connection.ip.as_str()))
+            .collect();
+        assert_eq!(connections, vec![("AUIECTSRND", "1.2.3.4"), ("QBUAZIFURK", "1.2.3.4"), ("QBUAZIFURK", "5.6.7.8")]);
+    }
+
+
+    #[test]
+    fn record_and_replay() {
+        use super::http_services::{RecordingService, ReplayService};
+
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/_synapse/admin/v1/reset_password/@alice:example.org")
+            .body(json!({ "new_password": "hunter2" }))
+            .respond_json(StatusCode::OK, json!({})));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_synapse/admin/v1/users/@alice:example.org/admin")
+            .respond_json(StatusCode::OK, json!({ "admin": true })));
+
+        let path = std::env::temp_dir().join(format!("synadminctl-cassette-{}.ron", std::process::id()));
+        let recording = super::MatrixService::new(
+            RecordingService::new(mock, path.clone()), BASE_URL.to_string(), "secret_token".to_string());
+        smol::run(recording.call(super::reset_password::Request::new(user_id(), "hunter2".to_string(), None))).unwrap();
+        smol::run(recording.call(super::user_is_admin::Request::new(user_id()))).unwrap();
+
+        let cassette = std::fs::read_to_string(&path).unwrap();
+        assert!(!cassette.contains("hunter2"));
+        assert!(!cassette.contains("secret_token"));
+
+        let replay = super::MatrixService::new(
+            ReplayService::load(&path).unwrap(), BASE_URL.to_string(), "access_token".to_string());
+        std::fs::remove_file(&path).unwrap();
+        smol::run(replay.call(super::reset_password::Request::new(user_id(), "hunter2".to_string(), None))).unwrap();
+        let response = smol::run(replay.call(super::user_is_admin::Request::new(user_id()))).unwrap();
+        assert!(response.admin);
+        // the recording is exhausted
+        assert!(smol::run(replay.call(super::user_is_admin::Request::new(user_id()))).is_err());
+    }
+
+    #[test]
+    fn redact_register_body() {
+        let body = json!({
+            "nonce": "nonce123",
+            "username": "alice",
+            "password": "hunter2",
+            "mac": "fa07619ad75fee1e5c70a5f20acd259f2f83f115",
+            "admin": false,
+        });
+        let redacted = super::http_services::redact_body(body.to_string().as_bytes());
+        let redacted: serde_json::Value = serde_json::from_str(&redacted).unwrap();
+        assert_eq!(redacted, json!({
+            "nonce": "<redacted>",
+            "username": "alice",
+            "password": "<redacted>",
+            "mac": "<redacted>",
+            "admin": false,
+        }));
+    }
+
+
+    #[test]
+    fn redact_uri() {
+        let uri: http::Uri = "https://matrix.example.org/_synapse/admin/v2/users?access_token=secret_token&limit=10".parse().unwrap();
+        assert_eq!(super::http_services::redact_uri(&uri),
+            "https://matrix.example.org/_synapse/admin/v2/users?access_token=<redacted>&limit=10");
+        let uri: http::Uri = "https://matrix.example.org/_synapse/admin/v2/users?from=0&access_token=secret_token".parse().unwrap();
+        assert_eq!(super::http_services::redact_uri(&uri),
+            "https://matrix.example.org/_synapse/admin/v2/users?from=0&access_token=<redacted>");
+        let uri: http::Uri = "https://matrix.example.org/_synapse/admin/v2/users?my_access_token=kept".parse().unwrap();
+        assert_eq!(super::http_services::redact_uri(&uri),
+            "https://matrix.example.org/_synapse/admin/v2/users?my_access_token=kept");
+        let uri: http::Uri = "https://matrix.example.org/_synapse/admin/v1/server_version".parse().unwrap();
+        assert_eq!(super::http_services::redact_uri(&uri), "https://matrix.example.org/_synapse/admin/v1/server_version");
+    }
+
+    #[test]
+    fn redact_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert(http::header::AUTHORIZATION, "Bearer secret_token".parse().unwrap());
+        headers.insert(http::header::CONTENT_TYPE, "application/json".parse().unwrap());
+        let mut redacted = super::http_services::redact_headers(&headers);
+        redacted.sort();
+        assert_eq!(redacted, vec![
+            ("authorization".to_string(), "<redacted>".to_string()),
+            ("content-type".to_string(), "application/json".to_string()),
+        ]);
+    }
+
+    fn http_request(method: Method, uri: &str) -> http::Request<Vec<u8>> {
+        http::Request::builder().method(method).uri(uri).body(vec![]).unwrap()
+    }
+
+    #[test]
+    fn retry_rate_limited() {
+        use super::http_services::RetryService;
+
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/limited")
+            .respond_json(StatusCode::TOO_MANY_REQUESTS, json!({ "errcode": "M_LIMIT_EXCEEDED", "retry_after_ms": 1 })));
+
+        // not processed by the server, so retried also for non-idempotent methods
+        let service = RetryService::new(mock.clone(), 3);
+        let response = smol::run(service.call(http_request(Method::POST, "https://matrix.example.org/limited"))).unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[test]
+    fn retry_server_errors_of_idempotent_methods() {
+        use super::http_services::RetryService;
+
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/unavailable")
+            .respond(StatusCode::SERVICE_UNAVAILABLE, ""));
+        mock.expect(Expectation::new(Method::POST, "https://matrix.example.org/unavailable")
+            .respond(StatusCode::SERVICE_UNAVAILABLE, ""));
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/broken")
+            .respond(StatusCode::INTERNAL_SERVER_ERROR, ""));
+
+        let service = RetryService::new(mock.clone(), 1);
+        let response = smol::run(service.call(http_request(Method::GET, "https://matrix.example.org/unavailable"))).unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(mock.requests().len(), 2);
+
+        // might have been processed, so not retried
+        let response = smol::run(service.call(http_request(Method::POST, "https://matrix.example.org/unavailable"))).unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(mock.requests().len(), 3);
+
+        // not a transient error
+        let response = smol::run(service.call(http_request(Method::GET, "https://matrix.example.org/broken"))).unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[test]
+    fn retry_connection_errors_of_idempotent_methods() {
+        use super::http_services::RetryService;
+
+        // requests without expectation fail like connection errors
+        let mock = MockService::new();
+        let service = RetryService::new(mock.clone(), 1);
+        assert!(smol::run(service.call(http_request(Method::GET, "https://matrix.example.org/unreachable"))).is_err());
+        assert_eq!(mock.requests().len(), 2);
+        assert!(smol::run(service.call(http_request(Method::POST, "https://matrix.example.org/unreachable"))).is_err());
+        assert_eq!(mock.requests().len(), 3);
+
+        let mock = MockService::new();
+        let service = RetryService::new(mock.clone(), 1).retry_non_idempotent(true);
+        assert!(smol::run(service.call(http_request(Method::POST, "https://matrix.example.org/unreachable"))).is_err());
+        assert_eq!(mock.requests().len(), 2);
+    }
+
+    #[test]
+    fn retry_disabled() {
+        use super::http_services::RetryService;
+
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/limited")
+            .respond_json(StatusCode::TOO_MANY_REQUESTS, json!({ "errcode": "M_LIMIT_EXCEEDED", "retry_after_ms": 1 })));
+
+        let service = RetryService::new(mock.clone(), 0);
+        let response = smol::run(service.call(http_request(Method::GET, "https://matrix.example.org/limited"))).unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        use super::http_services::{backoff, BACKOFF_MAX};
+
+        let first = backoff(0);
+        assert!(first >= std::time::Duration::from_millis(250) && first < std::time::Duration::from_millis(500));
+        for retry in &[10, 31, 32, 100, u32::MAX] {
+            let delay = backoff(*retry);
+            assert!(delay >= BACKOFF_MAX / 2 && delay < BACKOFF_MAX);
+        }
+    }
+
+    #[test]
+    fn rate_limit_delay() {
+        use super::http_services::{rate_limit_delay, BACKOFF_MAX};
+
+        let response = |body: serde_json::Value| {
+            let mut response = http::Response::new(body.to_string().into_bytes());
+            *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+            response
+        };
+        assert_eq!(rate_limit_delay(&response(json!({ "errcode": "M_LIMIT_EXCEEDED", "retry_after_ms": 1500 }))),
+            Some(std::time::Duration::from_millis(1500)));
+        // a day is too long to wait
+        assert_eq!(rate_limit_delay(&response(json!({ "errcode": "M_LIMIT_EXCEEDED", "retry_after_ms": 86400000 }))),
+            Some(BACKOFF_MAX));
+        assert_eq!(rate_limit_delay(&response(json!({ "errcode": "M_LIMIT_EXCEEDED" }))), None);
+        assert_eq!(rate_limit_delay(&response(json!({ "errcode": "M_FORBIDDEN", "retry_after_ms": 1500 }))), None);
+    }
+
+
+    #[test]
+    fn session_from_access_token() {
+        let mock = MockService::new();
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_matrix/client/r0/account/whoami")
+            .respond_json(StatusCode::OK, json!({ "user_id": "@alice:example.org" })));
+
+        let session = smol::run(super::session_from_access_token(
+            mock, BASE_URL.to_string(), "access_token".to_string())).unwrap();
+        assert_eq!(session.user_id, "@alice:example.org");
+        assert_eq!(session.base_url, BASE_URL);
+        assert_eq!(session.access_token, "access_token");
+    }
+
+
+    fn expect_well_known(mock: &MockService, status: StatusCode, body: &str) {
+        mock.expect(Expectation::new(Method::GET, "https://example.org/.well-known/matrix/client")
+            .respond(status, body));
+    }
+
+    fn expect_versions(mock: &MockService) {
+        mock.expect(Expectation::new(Method::GET, "https://matrix.example.org/_matrix/client/versions")
+            .respond_json(StatusCode::OK, json!({ "versions": ["r0.6.0"], "unstable_features": {} })));
+    }
+
+    #[test]
+    fn discovery_success() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{ "m.homeserver": { "base_url": "https://matrix.example.org" } }"#);
+        expect_versions(&mock);
+
+        let discovery_info = smol::run(super::server_discovery(mock, "@alice:example.org".to_string())).unwrap();
+        assert_eq!(discovery_info.homeserver.base_url, "https://matrix.example.org");
+        assert!(discovery_info.identity_server.is_none());
+    }
+
+    #[test]
+    fn discovery_without_server_name() {
+        let mock = MockService::new();
+
+        let result = smol::run(super::server_discovery(mock, "alice".to_string()));
+        assert_eq!(result.unwrap_err(), AutoDiscoveryError::Prompt);
+    }
+
+    #[test]
+    fn discovery_not_found() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::NOT_FOUND, r#"{ "errcode": "M_NOT_FOUND", "error": "Not found" }"#);
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert_eq!(result.unwrap_err(), AutoDiscoveryError::Prompt);
+    }
+
+    #[test]
+    fn discovery_server_error() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::INTERNAL_SERVER_ERROR, r#"{ "errcode": "M_UNKNOWN", "error": "Internal server error" }"#);
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailPrompt(_))));
+    }
+
+    #[test]
+    fn discovery_invalid_json() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, "<html>this is not json</html>");
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailPrompt(_))));
+    }
+
+    #[test]
+    fn discovery_missing_base_url() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{ "m.homeserver": {} }"#);
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailPrompt(_))));
+    }
+
+    #[test]
+    fn discovery_invalid_base_url() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{ "m.homeserver": { "base_url": "not a url" } }"#);
+
+        // fails before any request to the base_url, as there is no /versions expectation
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailError(reason)) if reason.starts_with("invalid base_url")));
+
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{ "m.homeserver": { "base_url": "matrix.example.org" } }"#);
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailError(reason)) if reason.starts_with("invalid base_url")));
+    }
+
+    #[test]
+    fn discovery_not_a_homeserver() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{ "m.homeserver": { "base_url": "https://matrix.example.org" } }"#);
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailError(_))));
+    }
+
+    #[test]
+    fn discovery_identity_server() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{
+            "m.homeserver": { "base_url": "https://matrix.example.org" },
+            "m.identity_server": { "base_url": "https://identity.example.org" }
+        }"#);
+        expect_versions(&mock);
+        mock.expect(Expectation::new(Method::GET, "https://identity.example.org/_matrix/identity/api/v1")
+            .respond_json(StatusCode::OK, json!({})));
+
+        let discovery_info = smol::run(super::server_discovery(mock, "@alice:example.org".to_string())).unwrap();
+        assert_eq!(discovery_info.identity_server.unwrap().base_url, "https://identity.example.org");
+    }
+
+    #[test]
+    fn discovery_invalid_identity_server() {
+        let mock = MockService::new();
+        expect_well_known(&mock, StatusCode::OK, r#"{
+            "m.homeserver": { "base_url": "https://matrix.example.org" },
+            "m.identity_server": { "base_url": "https://identity.example.org" }
+        }"#);
+        expect_versions(&mock);
+
+        let result = smol::run(super::server_discovery(mock, "@alice:example.org".to_string()));
+        assert!(matches!(result, Err(AutoDiscoveryError::FailError(_))));
+    }
+}
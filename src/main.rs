@@ -3,16 +3,21 @@
 use assign::assign;
 
 use std::io::Write;
+use futures::TryStreamExt;
 use synadminctl::{Session, Service};
 use structopt::StructOpt;
 use smol::unblock;
 use std::convert::TryInto;
 
+mod output;
+mod profiles;
 
+
+// prompts go to stderr, so that stdout only contains the command output
 fn prompt_cleartext(query: &str) -> String {
-    print!("{}: ", query);
+    eprint!("{}: ", query);
     // TODO: unwrap, forward io error?
-    std::io::stdout().flush().unwrap();
+    std::io::stderr().flush().unwrap();
     let mut reply = String::new();
     // TODO: unwrap, forward io error?
     std::io::stdin().read_line(&mut reply).unwrap();
@@ -20,26 +25,75 @@ fn prompt_cleartext(query: &str) -> String {
 }
 
 
-fn load_session() -> Result<synadminctl::Session, anyhow::Error> {
-    let file = std::fs::File::open("session.ron")?;
-    let reader = std::io::BufReader::new(file);
-    let session = ron::de::from_reader(reader)?;
-    Ok(session)
-}
-
-fn store_session(session: synadminctl::Session) -> Result<Session, anyhow::Error> {
-    let serialized = ron::ser::to_string_pretty(&session, ron::ser::PrettyConfig::default())?;
-
-    let file = std::fs::File::create("session.ron")?;
-    let mut buffer = std::io::BufWriter::new(file);
-    write!(&mut buffer, "{}", serialized)?;
-    Ok(session)
-}
-
-
 #[derive(StructOpt)]
 #[structopt(about = "synapse admin command-line interface")]
-enum Opt {
+struct Opt {
+    /// Output format of command results
+    #[structopt(short, long, global = true, default_value = "table", possible_values = output::OutputFormat::VARIANTS)]
+    output: output::OutputFormat,
+    /// Log http requests to stderr, with secrets redacted. Repeat to include headers and bodies.
+    #[structopt(short, long, global = true, parse(from_occurrences))]
+    verbose: u8,
+    /// How often to retry rate limited requests, and idempotent requests failing with server or connection errors
+    #[structopt(long, global = true, default_value = "3")]
+    max_retries: u32,
+    /// Do not retry failed requests, same as --max-retries 0
+    #[structopt(long, global = true)]
+    no_retry: bool,
+    /// Record all http requests and responses to this cassette file, with secrets redacted
+    #[structopt(long, global = true, parse(from_os_str))]
+    record: Option<std::path::PathBuf>,
+    /// Serve http responses from this cassette file instead of contacting the server
+    #[structopt(long, global = true, parse(from_os_str), conflicts_with = "record")]
+    replay: Option<std::path::PathBuf>,
+    /// Name of the stored session to use, instead of the default profile
+    #[structopt(long, global = true)]
+    profile: Option<String>,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, StructOpt)]
+enum Command {
+    /// Log in and store the session in the profile, replacing any previous one.
+    /// Username and password are taken from $SYNADMINCTL_USER and $SYNADMINCTL_PASSWORD if set.
+    Login {
+        /// Use an existing access token read from stdin instead of logging in with a password
+        #[structopt(long)]
+        token_stdin: bool,
+        /// Homeserver base url, instead of $SYNADMINCTL_HOMESERVER or discovering it from the username
+        #[structopt(long)]
+        homeserver: Option<String>,
+    },
+    /// Register a new user with the registration shared secret, which works without any existing account.
+    /// The password is taken from $SYNADMINCTL_PASSWORD if set.
+    Register {
+        /// Localpart of the new user
+        username: String,
+        /// File only containing the registration_shared_secret
+        #[structopt(long, parse(from_os_str), required_unless = "homeserver-config")]
+        shared_secret_file: Option<std::path::PathBuf>,
+        /// homeserver.yaml of synapse, to read the registration_shared_secret from
+        #[structopt(long, parse(from_os_str), conflicts_with = "shared-secret-file")]
+        homeserver_config: Option<std::path::PathBuf>,
+        /// Homeserver base url, instead of $SYNADMINCTL_HOMESERVER
+        #[structopt(long)]
+        homeserver: Option<String>,
+        #[structopt(long)]
+        displayname: Option<String>,
+        /// Make the new user a server admin
+        #[structopt(long)]
+        admin: bool,
+        /// Store the session of the new user in the profile, replacing any previous one
+        #[structopt(long)]
+        store_session: bool,
+    },
+    /// Log out the session of the profile, invalidating its access token, and remove the profile
+    Logout,
+    /// Show the user and homeserver of the current session, after checking that its access token is valid
+    Whoami,
+    /// Manage the stored sessions
+    Profiles(ProfilesCommand),
     Version,
     IsAdmin {
         #[structopt(long)]
@@ -58,17 +112,156 @@ enum Opt {
         user_id: String,
     },
     ListAccounts {
-        from: Option<js_int::UInt>,
+        /// Offset, as shown in next_token
+        from: Option<String>,
         limit: Option<js_int::UInt>,
+        /// Fetch all pages, starting at from and using limit as page size
+        #[structopt(long)]
+        all: bool,
         // TODO: user_id, name, guests, deactivated
     },
     ListRooms {
-        from: js_int::UInt,
+        from: Option<js_int::UInt>,
+        /// Maximum amount of rooms per page
+        #[structopt(long)]
+        limit: Option<js_int::UInt>,
+        /// Fetch all pages, starting at from and using limit as page size
+        #[structopt(long)]
+        all: bool,
+        #[structopt(long, possible_values = synadminctl::list_rooms::RoomOrderBy::VARIANTS)]
+        order_by: Option<synadminctl::list_rooms::RoomOrderBy>,
+        /// f for ascending, b for descending order
+        #[structopt(long, possible_values = synadminctl::Direction::VARIANTS)]
+        dir: Option<synadminctl::Direction>,
+        /// Only list rooms whose name contains this value
+        #[structopt(long)]
+        search: Option<String>,
     },
     PurgeRoom {
         #[structopt(long)]
         room_id: String,
     },
+    /// Delete a room in the background, and wait for the deletion to finish
+    DeleteRoom {
+        #[structopt(long)]
+        room_id: String,
+        /// Prevent future attempts to join the room
+        #[structopt(long)]
+        block: bool,
+        /// Only remove all local users from the room, but keep it in the database
+        #[structopt(long)]
+        no_purge: bool,
+        /// Purge the room even if local users cannot be removed from it
+        #[structopt(long, conflicts_with = "no-purge")]
+        force_purge: bool,
+        /// Move all local users into a new room, created by this user
+        #[structopt(long)]
+        new_room_user_id: Option<String>,
+        /// Name of the new room
+        #[structopt(long, requires = "new-room-user-id")]
+        room_name: Option<String>,
+        /// First message in the new room
+        #[structopt(long, requires = "new-room-user-id")]
+        message: Option<String>,
+        /// Only start the deletion, without waiting for it to finish
+        #[structopt(long)]
+        no_wait: bool,
+        /// Stop waiting after this long, e.g. 30m. The deletion continues on the server
+        #[structopt(long, parse(try_from_str = humantime::parse_duration), default_value = "1h", conflicts_with = "no-wait")]
+        timeout: std::time::Duration,
+    },
+    /// Show the details, members and state of a room
+    ShowRoom {
+        #[structopt(long)]
+        room_id: String,
+        /// Do not include the state events
+        #[structopt(long)]
+        no_state: bool,
+    },
+    /// Prevent local users from joining rooms, given as room IDs or aliases
+    BlockRoom {
+        #[structopt(required = true)]
+        rooms: Vec<String>,
+    },
+    /// Allow local users to join rooms again, given as room IDs or aliases
+    UnblockRoom {
+        #[structopt(required = true)]
+        rooms: Vec<String>,
+    },
+    /// Show whether rooms, given as room IDs or aliases, are blocked
+    IsRoomBlocked {
+        #[structopt(required = true)]
+        rooms: Vec<String>,
+    },
+    /// Make a local user admin of a room, e.g. after all room admins left
+    MakeRoomAdmin {
+        /// Room ID or alias
+        #[structopt(long)]
+        room: String,
+        /// Defaults to the logged in user
+        #[structopt(long)]
+        user_id: Option<String>,
+    },
+    /// Make local users join a room, e.g. an announcements room
+    ForceJoin {
+        /// Room ID or alias
+        #[structopt(long)]
+        room: String,
+        /// Read from stdin, one per line, if none are given
+        user_ids: Vec<String>,
+    },
+    /// Purge old events of a room in the background, and wait for the purge to finish
+    PurgeHistory {
+        #[structopt(long)]
+        room_id: String,
+        /// Purge events older than this, e.g. 180d
+        #[structopt(long, parse(try_from_str = humantime::parse_duration), required_unless = "up-to-event-id")]
+        older_than: Option<std::time::Duration>,
+        /// Purge events before this event
+        #[structopt(long, conflicts_with = "older-than")]
+        up_to_event_id: Option<String>,
+        /// Also purge events sent by local users, which are kept by default
+        #[structopt(long)]
+        delete_local_events: bool,
+        /// Only start the purge, without waiting for it to finish
+        #[structopt(long)]
+        no_wait: bool,
+        /// Stop waiting after this long, e.g. 30m. The purge continues on the server
+        #[structopt(long, parse(try_from_str = humantime::parse_duration), default_value = "1h", conflicts_with = "no-wait")]
+        timeout: std::time::Duration,
+    },
+    /// Show the status of all deletions of a room
+    DeleteRoomStatus {
+        #[structopt(long)]
+        room_id: String,
+    },
+    /// Deactivate an account after showing its details and asking for confirmation
+    DeactivateAccount {
+        #[structopt(long)]
+        user_id: String,
+        /// Also remove the profile and hide the messages of the user from users joining rooms later on
+        #[structopt(long)]
+        erase: bool,
+        /// Do not ask for confirmation
+        #[structopt(long)]
+        yes: bool,
+    },
+    /// Show from where and with which clients users connected.
+    /// IPs used by several of the given users are flagged.
+    Whois {
+        #[structopt(required = true)]
+        user_ids: Vec<String>,
+        #[structopt(long, default_value = "last-seen", possible_values = WhoisSortKey::VARIANTS)]
+        sort_by: WhoisSortKey,
+    },
+    /// Manage the tokens which allow to register on this server
+    RegistrationTokens(RegistrationTokensCommand),
+    /// Manage the devices of a user, e.g. to log out a compromised account
+    Devices(DevicesCommand),
+    /// Inspect and delete uploaded and cached media
+    Media(MediaCommand),
+    /// Triage events reported by users
+    Reports(ReportsCommand),
     ResetPassword {
         #[structopt(long)]
         user_id: String,
@@ -77,182 +270,1388 @@ enum Opt {
     },
 }
 
+#[derive(Clone, Copy, Debug)]
+enum WhoisSortKey {
+    LastSeen,
+    Ip,
+    User,
+    Device,
+}
+
+impl WhoisSortKey {
+    const VARIANTS: &'static [&'static str] = &["last-seen", "ip", "user", "device"];
+}
+
+impl std::str::FromStr for WhoisSortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last-seen" => Ok(WhoisSortKey::LastSeen),
+            "ip" => Ok(WhoisSortKey::Ip),
+            "user" => Ok(WhoisSortKey::User),
+            "device" => Ok(WhoisSortKey::Device),
+            _ => Err(format!("unknown sort key: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, StructOpt)]
+enum RegistrationTokensCommand {
+    /// List registration tokens
+    List {
+        /// Only list tokens which can still be used
+        #[structopt(long)]
+        valid: bool,
+        /// Only list tokens which are expired or used up
+        #[structopt(long, conflicts_with = "valid")]
+        invalid: bool,
+    },
+    /// Show a registration token, including its remaining uses
+    Show {
+        token: String,
+    },
+    /// Create one or more registration tokens
+    Create {
+        /// How many tokens to create
+        #[structopt(long, default_value = "1")]
+        count: u32,
+        /// Use this token instead of a random one, only possible when creating one token
+        #[structopt(long)]
+        token: Option<String>,
+        /// Length of the random tokens
+        #[structopt(long, conflicts_with = "token")]
+        length: Option<js_int::UInt>,
+        /// How often each token can be used, unlimited by default
+        #[structopt(long)]
+        uses_allowed: Option<js_int::UInt>,
+        /// How long the tokens can be used, e.g. 7d or 12h, forever by default
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        valid_for: Option<std::time::Duration>,
+    },
+    /// Change how often or how long a registration token can be used
+    Update {
+        token: String,
+        #[structopt(long)]
+        uses_allowed: Option<js_int::UInt>,
+        #[structopt(long, conflicts_with = "uses-allowed")]
+        unlimited_uses: bool,
+        /// From now on, e.g. 7d or 12h
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        valid_for: Option<std::time::Duration>,
+        #[structopt(long, conflicts_with = "valid-for")]
+        never_expire: bool,
+    },
+    /// Make registration tokens unusable immediately, while keeping them listed
+    Expire {
+        #[structopt(required = true)]
+        tokens: Vec<String>,
+    },
+    /// Delete registration tokens
+    Delete {
+        #[structopt(required = true)]
+        tokens: Vec<String>,
+    },
+}
+
+#[derive(Clone, StructOpt)]
+enum DevicesCommand {
+    /// List the devices of a user, including where they were last used
+    List {
+        #[structopt(long)]
+        user_id: String,
+    },
+    Show {
+        #[structopt(long)]
+        user_id: String,
+        device_id: String,
+    },
+    /// Change the display name of a device
+    Rename {
+        #[structopt(long)]
+        user_id: String,
+        device_id: String,
+        display_name: String,
+    },
+    /// Delete devices, invalidating their access tokens
+    Delete {
+        #[structopt(long)]
+        user_id: String,
+        #[structopt(required = true)]
+        device_ids: Vec<String>,
+    },
+    /// Delete all devices of a user, except for the given ones
+    Wipe {
+        #[structopt(long)]
+        user_id: String,
+        /// Keep this device, can be given multiple times
+        #[structopt(long)]
+        except: Vec<String>,
+    },
+}
+
+#[derive(Clone, StructOpt)]
+enum MediaCommand {
+    /// List the mxc:// URIs of all media in a room
+    Room {
+        #[structopt(long)]
+        room_id: String,
+    },
+    /// List the media uploaded by a user, with their sizes
+    List {
+        #[structopt(long)]
+        user_id: String,
+        from: Option<js_int::UInt>,
+        limit: Option<js_int::UInt>,
+        /// Fetch all pages, starting at from and using limit as page size
+        #[structopt(long)]
+        all: bool,
+    },
+    /// Delete media uploaded to this server, given as mxc:// URIs
+    Delete {
+        #[structopt(required = true)]
+        mxc_uris: Vec<String>,
+    },
+    /// Delete media uploaded by a user, at most limit at once
+    DeleteUserMedia {
+        #[structopt(long)]
+        user_id: String,
+        #[structopt(long)]
+        limit: Option<js_int::UInt>,
+    },
+    /// Delete media uploaded to this server which has not been accessed for some time
+    DeleteLocal {
+        /// Name of this server
+        #[structopt(long)]
+        server_name: String,
+        /// e.g. 90d
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        older_than: std::time::Duration,
+        /// Only delete media larger than this amount of bytes
+        #[structopt(long)]
+        larger_than: Option<js_int::UInt>,
+        /// Also delete media used as avatar of a user or room
+        #[structopt(long)]
+        include_profiles: bool,
+    },
+    /// Purge media cached from other servers which has not been accessed for some time
+    PurgeRemoteCache {
+        /// e.g. 30d
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        older_than: std::time::Duration,
+    },
+    /// Quarantine media given as mxc:// URIs on stdin, one per line, so that it cannot be downloaded anymore
+    Quarantine,
+    /// Remove media given as mxc:// URIs on stdin, one per line, from quarantine
+    Unquarantine,
+    /// Protect media uploaded to this server, given as mxc:// URIs on stdin, one per line, from being quarantined
+    Protect,
+    /// Allow media given as mxc:// URIs on stdin, one per line, to be quarantined again
+    Unprotect,
+    /// Quarantine all media in a room
+    QuarantineRoom {
+        #[structopt(long)]
+        room_id: String,
+    },
+    /// Quarantine all media uploaded by a user
+    QuarantineUser {
+        #[structopt(long)]
+        user_id: String,
+    },
+}
+
+#[derive(Clone, StructOpt)]
+enum ReportsCommand {
+    /// List event reports, newest first
+    List {
+        from: Option<js_int::UInt>,
+        limit: Option<js_int::UInt>,
+        /// Fetch all pages, starting at from and using limit as page size
+        #[structopt(long)]
+        all: bool,
+        /// Only list reports in rooms whose ID contains this value
+        #[structopt(long)]
+        room_id: Option<String>,
+        /// Only list reports by users whose ID contains this value
+        #[structopt(long)]
+        user_id: Option<String>,
+        /// List the oldest reports first
+        #[structopt(long)]
+        oldest_first: bool,
+    },
+    /// Show an event report
+    Show {
+        report_id: js_int::UInt,
+        /// Print the JSON of the reported event instead
+        #[structopt(long)]
+        event: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum MediaAction {
+    Quarantine,
+    Unquarantine,
+    Protect,
+    Unprotect,
+}
+
+/// Outcome of a media action for a single mxc:// URI
+#[derive(Debug, serde::Serialize)]
+struct MediaResult {
+    mxc_uri: String,
+    /// None if the action succeeded
+    error: Option<String>,
+}
+
+#[derive(Clone, StructOpt)]
+enum ProfilesCommand {
+    /// List all stored profiles
+    List,
+    /// Use this profile when no --profile is given
+    Use {
+        name: String,
+    },
+    /// Remove a stored profile. This does not log out its session.
+    Remove {
+        name: String,
+    },
+    /// Protect the access token of a profile with a passphrase, read from $SYNADMINCTL_PASSPHRASE or prompted for
+    Encrypt {
+        name: String,
+    },
+    /// Store the access token of a profile without passphrase again
+    Decrypt {
+        name: String,
+    },
+}
+
+const HOMESERVER_VARIABLE: &str = "SYNADMINCTL_HOMESERVER";
+const ACCESS_TOKEN_VARIABLE: &str = "SYNADMINCTL_ACCESS_TOKEN";
+const USER_VARIABLE: &str = "SYNADMINCTL_USER";
+const PASSWORD_VARIABLE: &str = "SYNADMINCTL_PASSWORD";
+
+type HttpService = synadminctl::http_services::RetryService<
+    synadminctl::http_services::LoggingService<synadminctl::http_services::BoxService>
+>;
+
+fn read_stdin() -> Result<String, std::io::Error> {
+    let mut input = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// Reads the registration shared secret from a file only containing it, or from the synapse configuration
+fn read_shared_secret(
+    shared_secret_file: Option<std::path::PathBuf>,
+    homeserver_config: Option<std::path::PathBuf>,
+) -> anyhow::Result<String> {
+    if let Some(path) = shared_secret_file {
+        return Ok(std::fs::read_to_string(path)?.trim().to_string());
+    }
+    let path = homeserver_config.ok_or_else(|| anyhow::anyhow!("no shared secret given"))?;
+    let config: serde_yaml::Value = serde_yaml::from_reader(std::fs::File::open(&path)?)?;
+    if let Some(shared_secret) = config["registration_shared_secret"].as_str() {
+        Ok(shared_secret.to_string())
+    } else if let Some(shared_secret_path) = config["registration_shared_secret_path"].as_str() {
+        Ok(std::fs::read_to_string(shared_secret_path)?.trim().to_string())
+    } else {
+        anyhow::bail!("{} does not contain a registration_shared_secret", path.display())
+    }
+}
+
+/// Logs in with a password, creating a new device.
+/// Username, password and homeserver are taken from the environment variables if set, and prompted for otherwise.
+/// Without a homeserver, it is discovered from the username.
+async fn password_login(http_service: &HttpService, homeserver: Option<String>) -> anyhow::Result<Session> {
+    let initial_device_display_name = format!("Synadminctl on {}", hostname::get().unwrap().into_string().unwrap());
+
+    let username = match std::env::var(USER_VARIABLE) {
+        Ok(username) => username,
+        Err(_) => unblock!(prompt_cleartext("username")),
+    };
+
+    let password = match std::env::var(PASSWORD_VARIABLE) {
+        Ok(password) => password,
+        Err(_) => unblock!(rpassword::prompt_password_stderr("password: "))?,
+    };
+
+    let homeserver = homeserver.or_else(|| std::env::var(HOMESERVER_VARIABLE).ok());
+    let discovery_info = if let Some(base_url) = homeserver {
+        ruma::api::client::r0::session::login::DiscoveryInfo {
+            homeserver: ruma::api::client::r0::session::login::HomeserverInfo { base_url },
+            identity_server: None,
+        }
+    } else {
+        // TODO: in addition, I should do a basic VersionResponse check to see whether the
+        // Admin API is available and configured, i.e. /_synapse might not be available
+        match synadminctl::server_discovery(http_service.clone(), username.clone()).await {
+            Ok(discovery_info) => discovery_info,
+            Err(synadminctl::AutoDiscoveryError::Prompt) => {
+                let base_url = unblock!(prompt_cleartext("homeserver url"));
+                ruma::api::client::r0::session::login::DiscoveryInfo {
+                    homeserver: ruma::api::client::r0::session::login::HomeserverInfo { base_url },
+                    identity_server: None,
+                }
+            },
+            Err(synadminctl::AutoDiscoveryError::FailPrompt(reason)) => {
+                eprintln!("Autodiscovery returned an error: {}", reason);
+                let base_url = unblock!(prompt_cleartext("homeserver url"));
+                ruma::api::client::r0::session::login::DiscoveryInfo {
+                    homeserver: ruma::api::client::r0::session::login::HomeserverInfo { base_url },
+                    identity_server: None,
+                }
+            },
+            Err(synadminctl::AutoDiscoveryError::FailError(reason)) => {
+                eprintln!("Autodiscovery returned an unrecoverable error: {}", reason);
+                panic!("Autodiscovery returned an unrecoverable error");
+            },
+            // TODO: this should not be needed here
+            Err(synadminctl::AutoDiscoveryError::Ignore) => {
+                unreachable!();
+            },
+        }
+    };
+
+    let service = synadminctl::AnonymousMatrixService::new(http_service.clone(), discovery_info.homeserver.base_url.clone());
+    let mut request = ruma::api::client::r0::session::login::Request::new(
+        ruma::api::client::r0::session::login::UserInfo::MatrixId(&username),
+        ruma::api::client::r0::session::login::LoginInfo::Password { password: &password },
+    );
+    request.initial_device_display_name = Some(&initial_device_display_name);
+    let response = service.call(request).await?;
+    let discovery_info = response.well_known.unwrap_or(discovery_info);
+
+    Ok(Session {
+        base_url: discovery_info.homeserver.base_url,
+        access_token: response.access_token,
+        user_id: response.user_id.to_string(),
+        device_id: response.device_id.to_string(),
+    })
+}
+
 fn main() -> anyhow::Result<()> {
     let opt = Opt::from_args();
-    let http_service = synadminctl::http_services::ReqwestService::new();
+
+    if let Command::Profiles(command) = opt.command {
+        return match command {
+            ProfilesCommand::List => output::print(opt.output, &profiles::list()?),
+            ProfilesCommand::Use { name } => profiles::set_default_profile(&name),
+            ProfilesCommand::Remove { name } => profiles::remove(&name),
+            ProfilesCommand::Encrypt { name } => profiles::encrypt(&name),
+            ProfilesCommand::Decrypt { name } => profiles::decrypt(&name),
+        };
+    }
+    let profile = match opt.profile.clone() {
+        Some(profile) => profile,
+        None => profiles::default_profile()?,
+    };
+
+    let transport = if let Some(cassette) = &opt.replay {
+        synadminctl::http_services::BoxService::new(
+            synadminctl::http_services::ReplayService::load(cassette)?,
+        )
+    } else if let Some(cassette) = &opt.record {
+        synadminctl::http_services::BoxService::new(
+            synadminctl::http_services::RecordingService::new(
+                synadminctl::http_services::ReqwestService::new(),
+                cassette.clone(),
+            ),
+        )
+    } else {
+        synadminctl::http_services::BoxService::new(
+            synadminctl::http_services::ReqwestService::new(),
+        )
+    };
+    let max_retries = if opt.no_retry { 0 } else { opt.max_retries };
+    // retries wrap logging, so that every attempt is logged
+    let http_service = synadminctl::http_services::RetryService::new(
+        synadminctl::http_services::LoggingService::new(transport, opt.verbose),
+        max_retries,
+    );
 
     smol::run(async {
-        let session = if let Ok(session) = unblock!(load_session()) {
-            session
-        } else {
-            // TODO: do a match case and print the error somehow, and differentiate between
-            // „file not found“ and other errors like permission denied or session.ron file has wrong format
-            println!("Initial Login:");
-            let initial_device_display_name = format!("Synadminctl on {}", hostname::get().unwrap().into_string().unwrap());
-
-            let username = unblock!(prompt_cleartext("username"));
-
-            // could also prompt on stderr, should I?
-            let password = unblock!(rpassword::prompt_password_stdout("password: "))?;
-
-            // TODO: in addition, I should do a basic VersionResponse check to see whether the
-            // Admin API is available and configured, i.e. /_synapse might not be available
-            let discovery_info = match synadminctl::server_discovery(http_service.clone(), username.clone()).await {
-                Ok(discovery_info) => discovery_info,
-                Err(synadminctl::AutoDiscoveryError::Prompt) => {
-                    let base_url = unblock!(prompt_cleartext("homeserver url"));
-                    ruma::api::client::r0::session::login::DiscoveryInfo {
-                        homeserver: ruma::api::client::r0::session::login::HomeserverInfo { base_url },
-                        identity_server: None,
+        if let Command::Register { username, shared_secret_file, homeserver_config, homeserver, displayname, admin, store_session } = opt.command {
+            let base_url = homeserver.or_else(|| std::env::var(HOMESERVER_VARIABLE).ok())
+                .ok_or_else(|| anyhow::anyhow!("register needs --homeserver or ${}", HOMESERVER_VARIABLE))?;
+            let shared_secret = unblock!(read_shared_secret(shared_secret_file, homeserver_config))?;
+            let password = match std::env::var(PASSWORD_VARIABLE) {
+                Ok(password) => password,
+                Err(_) => {
+                    let password = unblock!(rpassword::prompt_password_stderr("password: "))?;
+                    if unblock!(rpassword::prompt_password_stderr("repeat password: "))? != password {
+                        anyhow::bail!("passwords do not match");
                     }
-                },
-                Err(synadminctl::AutoDiscoveryError::FailPrompt(reason)) => {
-                    eprintln!("Autodiscovery returned an error: {}", reason);
-                    let base_url = unblock!(prompt_cleartext("homeserver url"));
-                    ruma::api::client::r0::session::login::DiscoveryInfo {
-                        homeserver: ruma::api::client::r0::session::login::HomeserverInfo { base_url },
-                        identity_server: None,
-                    }
-                },
-                Err(synadminctl::AutoDiscoveryError::FailError(reason)) => {
-                    eprintln!("Autodiscovery returned an unrecoverable error: {}", reason);
-                    panic!("Autodiscovery returned an unrecoverable error");
-                },
-                // TODO: this should not be needed here
-                Err(synadminctl::AutoDiscoveryError::Ignore) => {
-                    unreachable!();
+                    password
                 },
             };
 
-            let service = synadminctl::AnonymousMatrixService::new(http_service.clone(), discovery_info.homeserver.base_url.clone());
-            let mut request = ruma::api::client::r0::session::login::Request::new(
-                ruma::api::client::r0::session::login::UserInfo::MatrixId(&username),
-                ruma::api::client::r0::session::login::LoginInfo::Password { password: &password },
-            );
-            request.initial_device_display_name = Some(&initial_device_display_name);
-            let response = service.call(request).await?;
-            let discovery_info = response.well_known.unwrap_or(discovery_info);
+            let response = synadminctl::register_with_shared_secret(
+                http_service.clone(), base_url.clone(), &shared_secret, username, password, admin, displayname,
+            ).await?;
+            eprintln!("Registered {}", response.user_id);
+            output::print(opt.output, &response)?;
+            if store_session {
+                let session = Session {
+                    base_url,
+                    access_token: response.access_token,
+                    user_id: response.user_id.to_string(),
+                    device_id: response.device_id,
+                };
+                unblock!(profiles::store_session(&profile, session, None))?;
+            }
+            return Ok(());
+        }
 
-            let session = Session {
-                base_url: discovery_info.homeserver.base_url,
-                access_token: response.access_token,
-                user_id: response.user_id.to_string(),
-                device_id: response.device_id.to_string(),
+        if let Command::Login { token_stdin, homeserver } = opt.command {
+            let session = if token_stdin {
+                let base_url = homeserver.or_else(|| std::env::var(HOMESERVER_VARIABLE).ok())
+                    .ok_or_else(|| anyhow::anyhow!("--token-stdin needs --homeserver or ${}", HOMESERVER_VARIABLE))?;
+                let access_token = unblock!(read_stdin())?;
+                synadminctl::session_from_access_token(http_service.clone(), base_url, access_token).await?
+            } else {
+                password_login(&http_service, homeserver).await?
             };
+            eprintln!("Logged in as {} on {}", session.user_id, session.base_url);
+            unblock!(profiles::store_session(&profile, session, None))?;
+            return Ok(());
+        }
 
-
-            unblock!(store_session(session.clone()))?
+        let session_from_environment = std::env::var(HOMESERVER_VARIABLE).is_ok() && std::env::var(ACCESS_TOKEN_VARIABLE).is_ok();
+        let mut encrypted = false;
+        let session = if let (Ok(base_url), Ok(access_token)) = (std::env::var(HOMESERVER_VARIABLE), std::env::var(ACCESS_TOKEN_VARIABLE)) {
+            // not stored, as it is already stored wherever the environment variable comes from
+            synadminctl::session_from_access_token(http_service.clone(), base_url, access_token).await?
+        } else {
+            let profile_name = profile.clone();
+            match unblock!(profiles::load_session(&profile_name))? {
+                Some(stored_session) => {
+                    encrypted = stored_session.is_encrypted();
+                    let profile_name = profile.clone();
+                    unblock!(stored_session.unlock(&profile_name))?
+                },
+                // logging in only to log out again or to show who is logged in would be pointless
+                None if matches!(opt.command, Command::Logout | Command::Whoami) =>
+                    anyhow::bail!("no session stored for profile {}, the profile is not logged in", profile),
+                None => {
+                    eprintln!("Initial Login for profile {}:", profile);
+                    let session = password_login(&http_service, None).await?;
+                    let profile_name = profile.clone();
+                    unblock!(profiles::store_session(&profile_name, session, None))?
+                },
+            }
         };
 
 
         // TODO: also use the other stuff from DiscoveryInfo?
         // TODO: hand Session to constructor?
-        let service = synadminctl::MatrixService::new(http_service.clone(), session.base_url, session.access_token);
+        let service = synadminctl::MatrixService::new(http_service.clone(), session.base_url.clone(), session.access_token.clone());
 
-        let result = match opt {
-            Opt::Version => {
-                let request = synadminctl::version::Request::new();
-                let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
-            },
-            Opt::IsAdmin { user_id } => {
-                let request = synadminctl::user_is_admin::Request::new(
-                    user_id.try_into()?,
-                );
-                let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
-            },
-            Opt::QueryUser { user_id } => {
-                let request = synadminctl::query_user::Request::new(
-                    user_id.try_into()?,
-                );
-                println!("{:?}", request);
-                let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
+        match opt.command {
+            Command::Logout => {
+                let result = service.call(ruma::api::client::r0::session::logout::Request::new()).await;
+                match result {
+                    Ok(_) => {},
+                    // the session is useless anyways
+                    Err(error) if is_unknown_token(&error) => {},
+                    Err(error) => return Err(error.into()),
+                }
+                if session_from_environment {
+                    eprintln!("Logged out the access token from ${}", ACCESS_TOKEN_VARIABLE);
+                } else {
+                    let profile_name = profile.clone();
+                    unblock!(profiles::remove(&profile_name))?;
+                    eprintln!("Logged out and removed profile {}", profile);
+                }
+                return Ok(());
             },
-            Opt::ListJoinedRooms { user_id } => {
-                let request = synadminctl::list_joined_rooms::Request::new(
-                    user_id.try_into()?,
-                );
-                println!("{:?}", request);
-                let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
+            Command::Whoami => {
+                let response = service.call(ruma::api::client::r0::account::whoami::Request::new()).await?;
+                let whoami = Whoami {
+                    profile: if session_from_environment { None } else { Some(profile) },
+                    user_id: response.user_id.to_string(),
+                    device_id: session.device_id,
+                    base_url: session.base_url,
+                };
+                return output::print(opt.output, &whoami);
             },
-            Opt::CreateModifyAccount { user_id } => {
-                println!("new user creation");
-                let password = unblock!(prompt_cleartext("password"));
-                let displayname = unblock!(prompt_cleartext("displayname"));
-                let mail_address = unblock!(prompt_cleartext("mail address"));
-
-                // TODO: es gibt
-                // 1. setzen auf leer
-                // 2. setzen auf bestimmten wert (oder mehrere)
-                // 3. alten Wert so lassen wie er war.
-                // -> aktuell behandle ich leer als ignorieren
-                let threepids = if mail_address != "" {
-                    Some(vec![synadminctl::Threepid {
-                        medium: ruma::thirdparty::Medium::Email,
-                        address: mail_address,
-                    }])
+            _ => {},
+        }
+
+        match run(opt.command.clone(), &service, opt.output).await {
+            Err(error) if !session_from_environment && is_rejected_session(&error) => {
+                eprintln!("The access token of profile {} has been rejected by the server.", profile);
+                let answer = unblock!(prompt_cleartext("Log in again? [y/N]"));
+                if !answer.eq_ignore_ascii_case("y") {
+                    return Err(error);
+                }
+
+                let session = password_login(&http_service, Some(session.base_url)).await?;
+                let passphrase = if encrypted {
+                    let profile_name = profile.clone();
+                    Some(unblock!(profiles::passphrase(&format!("new passphrase for profile {}: ", profile_name)))?)
                 } else {
                     None
                 };
+                let profile_name = profile.clone();
+                let session = unblock!(profiles::store_session(&profile_name, session, passphrase.as_deref()))?;
 
-                let request = assign!(synadminctl::create_modify_account::Request::new(user_id.try_into()?, password), {
-                    displayname: Some(displayname),
-                    threepids,
-                });
-                println!("{:?}", request);
-                let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
+                let service = synadminctl::MatrixService::new(http_service.clone(), session.base_url, session.access_token);
+                run(opt.command, &service, opt.output).await
             },
-            Opt::ListAccounts { from, limit } => {
-                let request = assign!(synadminctl::list_accounts::Request::new(), {
-                    from,
-                    limit,
+            result => result,
+        }
+    })
+}
+
+#[derive(Debug, serde::Serialize)]
+struct RoomBlockStatus {
+    /// As given on the command line
+    room: String,
+    room_id: ruma::RoomId,
+    blocked: bool,
+    /// Not known after blocking a room
+    blocked_by: Option<ruma::UserId>,
+}
+
+/// Outcome of force-join for a single user
+#[derive(Debug, serde::Serialize)]
+struct JoinResult {
+    user_id: String,
+    /// None if joining failed
+    room_id: Option<ruma::RoomId>,
+    /// None if joining succeeded
+    error: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct PurgeStatus {
+    purge_id: String,
+    status: synadminctl::purge_history_status::Status,
+    /// only set if the status is failed
+    error: Option<String>,
+}
+
+/// A single connection of whois, flattened for sorting
+#[derive(Debug, serde::Serialize)]
+struct WhoisConnection {
+    user_id: ruma::UserId,
+    device_id: String,
+    ip: String,
+    last_seen: js_int::UInt,
+    user_agent: Option<String>,
+    /// Other users of the whois command which connected from the same IP
+    ip_also_used_by: Vec<ruma::UserId>,
+}
+
+/// Output of show-room for structured output formats
+#[derive(Debug, serde::Serialize)]
+struct RoomOverview<'a> {
+    details: &'a synadminctl::list_rooms::RoomDetails,
+    members: &'a [ruma::UserId],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<&'a Vec<synadminctl::room_state::StateEvent>>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Whoami {
+    /// None if the session is taken from the environment variables
+    profile: Option<String>,
+    user_id: String,
+    device_id: String,
+    base_url: String,
+}
+
+fn is_unknown_token(error: &synadminctl::MatrixLibError<ruma::api::client::Error>) -> bool {
+    match error {
+        synadminctl::MatrixLibError::FromHttpResponseError(ruma::api::error::FromHttpResponseError::Http(
+            ruma::api::error::ServerError::Known(error))) =>
+            matches!(error.kind, ruma::api::client::error::ErrorKind::UnknownToken { .. }),
+        _ => false,
+    }
+}
+
+/// Whether the command failed because the access token has been rejected, which can be fixed by logging in again
+fn is_rejected_session(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<synadminctl::MatrixLibError<ruma::api::client::Error>>().map_or(false, is_unknown_token)
+}
+
+/// Room IDs are used as is, room aliases are resolved with the room directory
+async fn resolve_room(
+    service: &synadminctl::MatrixService<HttpService>,
+    room: &str,
+) -> anyhow::Result<ruma::RoomId> {
+    if room.starts_with('#') {
+        let room_alias: ruma::RoomAliasId = room.try_into()?;
+        let request = ruma::api::client::r0::alias::get_alias::Request::new(&room_alias);
+        let response = service.call(request).await?;
+        Ok(response.room_id)
+    } else {
+        Ok(room.try_into()?)
+    }
+}
+
+async fn set_rooms_blocked(
+    rooms: Vec<String>,
+    block: bool,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    // all rooms are resolved first, so that a typo does not leave only some rooms blocked
+    let mut room_ids = vec![];
+    for room in &rooms {
+        room_ids.push(resolve_room(service, room).await?);
+    }
+
+    let mut statuses = vec![];
+    for (room, room_id) in rooms.into_iter().zip(room_ids) {
+        let response = service.call(synadminctl::block_room::Request::new(room_id.clone(), block)).await?;
+        statuses.push(RoomBlockStatus {
+            room,
+            room_id,
+            blocked: response.block,
+            blocked_by: None,
+        });
+    }
+    output::print(output_format, &statuses)
+}
+
+/// Polls the status of a room deletion until it is finished, showing its progress on stderr
+async fn wait_for_deletion(
+    service: &synadminctl::MatrixService<HttpService>,
+    delete_id: String,
+    timeout: std::time::Duration,
+) -> anyhow::Result<synadminctl::delete_room::DeleteStatus> {
+    let start = std::time::Instant::now();
+    loop {
+        let request = synadminctl::delete_status::Request::new(delete_id.clone());
+        let response = service.call(request).await?;
+        eprint!("\rdeleting room: {:<13} {:>4}s", response.status.as_str(), start.elapsed().as_secs());
+
+        if response.status.is_finished() {
+            eprintln!();
+            return Ok(synadminctl::delete_room::DeleteStatus {
+                delete_id,
+                status: response.status,
+                error: response.error,
+                shutdown_room: response.shutdown_room,
+            });
+        }
+        // statuses unknown to synadminctl are treated as still running, so there has to be an end
+        if start.elapsed() >= timeout {
+            eprintln!();
+            anyhow::bail!(
+                "room deletion {} is still {} after {}, check on it with delete-room-status",
+                delete_id, response.status, humantime::format_duration(timeout),
+            );
+        }
+        smol::Timer::new(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+/// Polls the status of a room history purge until it is finished, showing its progress on stderr
+async fn wait_for_purge(
+    service: &synadminctl::MatrixService<HttpService>,
+    purge_id: String,
+    timeout: std::time::Duration,
+) -> anyhow::Result<PurgeStatus> {
+    let start = std::time::Instant::now();
+    loop {
+        let request = synadminctl::purge_history_status::Request::new(purge_id.clone());
+        let response = service.call(request).await?;
+        eprint!("\rpurging history: {:<8} {:>4}s", response.status.as_str(), start.elapsed().as_secs());
+
+        if response.status.is_finished() {
+            eprintln!();
+            return Ok(PurgeStatus {
+                purge_id,
+                status: response.status,
+                error: response.error,
+            });
+        }
+        // statuses unknown to synadminctl are treated as still running, so there has to be an end
+        if start.elapsed() >= timeout {
+            eprintln!();
+            anyhow::bail!(
+                "history purge {} is still {} after {}",
+                purge_id, response.status, humantime::format_duration(timeout),
+            );
+        }
+        smol::Timer::new(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+async fn run(
+    command: Command,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        Command::Login { .. } | Command::Register { .. } | Command::Logout | Command::Whoami | Command::Profiles(_) =>
+            unreachable!("handled before a session is needed"),
+        Command::Version => {
+            let request = synadminctl::version::Request::new();
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::IsAdmin { user_id } => {
+            let request = synadminctl::user_is_admin::Request::new(
+                user_id.try_into()?,
+            );
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::QueryUser { user_id } => {
+            let request = synadminctl::query_user::Request::new(
+                user_id.try_into()?,
+            );
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::ListJoinedRooms { user_id } => {
+            let request = synadminctl::list_joined_rooms::Request::new(
+                user_id.try_into()?,
+            );
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::CreateModifyAccount { user_id } => {
+            eprintln!("new user creation");
+            let password = unblock!(prompt_cleartext("password"));
+            let displayname = unblock!(prompt_cleartext("displayname"));
+            let mail_address = unblock!(prompt_cleartext("mail address"));
+
+            // TODO: es gibt
+            // 1. setzen auf leer
+            // 2. setzen auf bestimmten wert (oder mehrere)
+            // 3. alten Wert so lassen wie er war.
+            // -> aktuell behandle ich leer als ignorieren
+            let threepids = if mail_address != "" {
+                Some(vec![synadminctl::Threepid {
+                    medium: ruma::thirdparty::Medium::Email,
+                    address: mail_address,
+                }])
+            } else {
+                None
+            };
+
+            let request = assign!(synadminctl::create_modify_account::Request::new(user_id.try_into()?, password), {
+                displayname: Some(displayname),
+                threepids,
+            });
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::ListAccounts { from, limit, all } => {
+            let request = assign!(synadminctl::list_accounts::Request::new(), {
+                from,
+                limit,
+            });
+            if all {
+                let users: Vec<_> = synadminctl::paginate(&service, request).try_collect().await?;
+                output::print(output_format, &users)?;
+            } else {
+                let response = service.call(request).await?;
+                output::print(output_format, &response)?;
+            }
+            Ok(())
+        },
+        Command::ListRooms { from, limit, all, order_by, dir, search } => {
+            let request = assign!(synadminctl::list_rooms::Request::new(), {
+                from,
+                limit,
+                order_by,
+                dir,
+                search_term: search,
+            });
+            if all {
+                let rooms: Vec<_> = synadminctl::paginate(&service, request).try_collect().await?;
+                output::print(output_format, &rooms)?;
+            } else {
+                let response = service.call(request).await?;
+                output::print(output_format, &response)?;
+            }
+            Ok(())
+        },
+        Command::PurgeRoom { room_id } => {
+            eprintln!("room purging");
+            let request = synadminctl::purge_room::Request::new(
+                room_id.try_into()?,
+            );
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::DeleteRoom { room_id, block, no_purge, force_purge, new_room_user_id, room_name, message, no_wait, timeout } => {
+            let new_room_user_id: Option<ruma::UserId> = new_room_user_id.map(|user_id| user_id.try_into()).transpose()?;
+            let request = assign!(synadminctl::delete_room::Request::new(room_id.try_into()?), {
+                new_room_user_id,
+                room_name,
+                message,
+                block: Some(block),
+                purge: Some(!no_purge),
+                force_purge: Some(force_purge),
+            });
+            let response = service.call(request).await?;
+            if no_wait {
+                output::print(output_format, &response)?;
+                return Ok(());
+            }
+
+            let status = wait_for_deletion(service, response.delete_id, timeout).await?;
+            output::print(output_format, &status)?;
+            if status.status == synadminctl::delete_room::Status::Failed {
+                anyhow::bail!("room deletion failed: {}", status.error.unwrap_or_default());
+            }
+            Ok(())
+        },
+        Command::ShowRoom { room_id, no_state } => {
+            let room_id: ruma::RoomId = room_id.try_into()?;
+            let details = service.call(synadminctl::room_details::Request::new(room_id.clone())).await?;
+            let members = service.call(synadminctl::room_members::Request::new(room_id.clone())).await?;
+            let state = if no_state {
+                None
+            } else {
+                Some(service.call(synadminctl::room_state::Request::new(room_id)).await?)
+            };
+
+            if output_format.is_structured() {
+                output::print_structured(output_format, &RoomOverview {
+                    details: &details.room,
+                    members: &members.members,
+                    state: state.as_ref().map(|state| &state.state),
+                })?;
+            } else {
+                // the sections have different columns, so they are printed one after another
+                output::print(output_format, &details)?;
+                println!();
+                output::print(output_format, &members)?;
+                if let Some(state) = &state {
+                    println!();
+                    output::print(output_format, state)?;
+                }
+            }
+            Ok(())
+        },
+        Command::BlockRoom { rooms } => set_rooms_blocked(rooms, true, service, output_format).await,
+        Command::UnblockRoom { rooms } => set_rooms_blocked(rooms, false, service, output_format).await,
+        Command::IsRoomBlocked { rooms } => {
+            let mut statuses = vec![];
+            for room in rooms {
+                let room_id = resolve_room(service, &room).await?;
+                let response = service.call(synadminctl::room_block_status::Request::new(room_id.clone())).await?;
+                statuses.push(RoomBlockStatus {
+                    room,
+                    room_id,
+                    blocked: response.block,
+                    blocked_by: response.user_id,
+                });
+            }
+            output::print(output_format, &statuses)?;
+            Ok(())
+        },
+        Command::MakeRoomAdmin { room, user_id } => {
+            let user_id: Option<ruma::UserId> = user_id.map(|user_id| user_id.try_into()).transpose()?;
+            let request = synadminctl::make_room_admin::Request::new(room.as_str().try_into()?, user_id);
+            service.call(request).await?;
+            eprintln!("granted room admin in {}", room);
+            Ok(())
+        },
+        Command::ForceJoin { room, user_ids } => {
+            let room_id_or_alias: ruma::RoomIdOrAliasId = room.as_str().try_into()?;
+            let user_ids = if user_ids.is_empty() {
+                let input = unblock!(read_stdin())?;
+                input.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect()
+            } else {
+                user_ids
+            };
+
+            let mut results = vec![];
+            for user_id in user_ids {
+                let result: anyhow::Result<ruma::RoomId> = async {
+                    let request = synadminctl::join_room::Request::new(room_id_or_alias.clone(), user_id.as_str().try_into()?);
+                    Ok(service.call(request).await?.room_id)
+                }.await;
+                let result = match result {
+                    // would fail for every user, and is only handled if it reaches the login prompt
+                    Err(error) if is_rejected_session(&error) => return Err(error),
+                    result => result,
+                };
+                results.push(JoinResult {
+                    user_id,
+                    room_id: result.as_ref().ok().cloned(),
+                    error: result.err().map(|error| error.to_string()),
+                });
+            }
+            output::print(output_format, &results)?;
+
+            let failed = results.iter().filter(|result| result.error.is_some()).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} users could not be joined", failed, results.len());
+            }
+            Ok(())
+        },
+        Command::PurgeHistory { room_id, older_than, up_to_event_id, delete_local_events, no_wait, timeout } => {
+            let purge_up_to_event_id: Option<ruma::EventId> = up_to_event_id.map(|event_id| event_id.try_into()).transpose()?;
+            let purge_up_to_ts = older_than.map(milliseconds_ago).transpose()?;
+            let request = assign!(synadminctl::purge_history::Request::new(room_id.try_into()?), {
+                delete_local_events: Some(delete_local_events),
+                purge_up_to_event_id,
+                purge_up_to_ts,
+            });
+            let response = service.call(request).await?;
+            if no_wait {
+                output::print(output_format, &response)?;
+                return Ok(());
+            }
+
+            let status = wait_for_purge(service, response.purge_id, timeout).await?;
+            output::print(output_format, &status)?;
+            if status.status == synadminctl::purge_history_status::Status::Failed {
+                anyhow::bail!("purging room history failed: {}", status.error.unwrap_or_default());
+            }
+            Ok(())
+        },
+        Command::DeleteRoomStatus { room_id } => {
+            let request = synadminctl::room_delete_status::Request::new(
+                room_id.try_into()?,
+            );
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::DeactivateAccount { user_id, erase, yes } => {
+            let user_id: ruma::UserId = user_id.try_into()?;
+            let user = service.call(synadminctl::query_user::Request::new(user_id.clone())).await?;
+            // deactivating again changes nothing, only erasing does
+            if user.deactivated && !erase {
+                eprintln!("{} is already deactivated, nothing to do", user_id);
+                return Ok(());
+            }
+            if !yes {
+                // on stderr like the prompt, so that stdout only contains the command output
+                eprintln!("user:         {}", user_id);
+                eprintln!("display name: {}", user.displayname.as_deref().unwrap_or_default());
+                eprintln!("threepids:    {}", user.threepids.iter().flatten()
+                    .map(|threepid| threepid.address.as_str()).collect::<Vec<_>>().join(", "));
+                eprintln!("admin:        {}", user.admin);
+                let question = if user.deactivated {
+                    format!("{} is already deactivated. Erase it? This cannot be undone. [y/N]", user_id)
+                } else if erase {
+                    format!("Deactivate and erase {}? This cannot be undone. [y/N]", user_id)
+                } else {
+                    format!("Deactivate {}? This cannot be undone. [y/N]", user_id)
+                };
+                let answer = unblock!(prompt_cleartext(&question));
+                if !answer.eq_ignore_ascii_case("y") {
+                    anyhow::bail!("aborted, {} has not been deactivated", user_id);
+                }
+            }
+
+            let request = synadminctl::deactivate_account::Request::new(user_id.clone(), Some(erase));
+            let response = service.call(request).await?;
+            eprintln!("Deactivated {}, logged out all its devices and removed it from all rooms", user_id);
+            if erase {
+                eprintln!("Removed its profile and marked its messages as erased");
+            }
+            if response.id_server_unbind_result != "success" {
+                eprintln!("Its threepids could not be unbound from all identity servers: {}", response.id_server_unbind_result);
+            }
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+        Command::Whois { user_ids, sort_by } => {
+            let mut connections = vec![];
+            for user_id in user_ids {
+                let response = service.call(synadminctl::whois::Request::new(user_id.try_into()?)).await?;
+                connections.extend(response.connections().map(|(device_id, connection)| WhoisConnection {
+                    user_id: response.user_id.clone(),
+                    device_id: device_id.to_string(),
+                    ip: connection.ip.clone(),
+                    last_seen: connection.last_seen,
+                    user_agent: connection.user_agent.clone(),
+                    ip_also_used_by: vec![],
+                }));
+            }
+
+            let mut users_by_ip: std::collections::HashMap<String, std::collections::BTreeSet<ruma::UserId>> = Default::default();
+            for connection in &connections {
+                users_by_ip.entry(connection.ip.clone()).or_default().insert(connection.user_id.clone());
+            }
+            for connection in &mut connections {
+                connection.ip_also_used_by = users_by_ip[&connection.ip].iter()
+                    .filter(|user_id| **user_id != connection.user_id)
+                    .cloned()
+                    .collect();
+            }
+
+            match sort_by {
+                // most recent first
+                WhoisSortKey::LastSeen => connections.sort_by(|a, b| b.last_seen.cmp(&a.last_seen)),
+                WhoisSortKey::Ip => connections.sort_by(|a, b| a.ip.cmp(&b.ip)),
+                WhoisSortKey::User => connections.sort_by(|a, b| a.user_id.as_str().cmp(b.user_id.as_str())),
+                WhoisSortKey::Device => connections.sort_by(|a, b| a.device_id.cmp(&b.device_id)),
+            }
+            let shared = connections.iter().filter(|connection| !connection.ip_also_used_by.is_empty()).count();
+            if shared > 0 {
+                eprintln!("{} connections from IPs used by several of the given users", shared);
+            }
+            output::print(output_format, &connections)?;
+            Ok(())
+        },
+        Command::RegistrationTokens(command) => run_registration_tokens(command, service, output_format).await,
+        Command::Devices(command) => run_devices(command, service, output_format).await,
+        Command::Media(command) => run_media(command, service, output_format).await,
+        Command::Reports(command) => run_reports(command, service, output_format).await,
+        Command::ResetPassword { user_id, logout_devices } => {
+            // TODO: option for random generation
+            let new_password = rpassword::prompt_password_stderr("new password: ").unwrap();
+
+            let request = synadminctl::reset_password::Request::new(
+                user_id.try_into()?,
+                new_password,
+                Some(logout_devices),
+            );
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+            Ok(())
+        },
+    }
+}
+
+/// Milliseconds since the unix epoch, as used by synapse
+fn milliseconds_since_epoch(time: std::time::SystemTime) -> anyhow::Result<js_int::UInt> {
+    let milliseconds = time.duration_since(std::time::UNIX_EPOCH)?.as_millis();
+    std::convert::TryFrom::try_from(milliseconds).ok()
+        .and_then(js_int::UInt::new)
+        .ok_or_else(|| anyhow::anyhow!("time is too far in the future"))
+}
+
+/// Milliseconds since the unix epoch, the given duration from now
+fn milliseconds_from_now(duration: std::time::Duration) -> anyhow::Result<js_int::UInt> {
+    let time = std::time::SystemTime::now().checked_add(duration)
+        .ok_or_else(|| anyhow::anyhow!("{} from now is too far in the future", humantime::format_duration(duration)))?;
+    milliseconds_since_epoch(time)
+}
+
+/// Milliseconds since the unix epoch, the given duration ago
+fn milliseconds_ago(duration: std::time::Duration) -> anyhow::Result<js_int::UInt> {
+    let time = std::time::SystemTime::now().checked_sub(duration)
+        .ok_or_else(|| anyhow::anyhow!("{} ago is too far in the past", humantime::format_duration(duration)))?;
+    milliseconds_since_epoch(time)
+}
+
+/// Splits mxc://<server_name>/<media_id> into server name and media ID
+fn parse_mxc_uri(uri: &str) -> anyhow::Result<(String, String)> {
+    let invalid = || anyhow::anyhow!("invalid mxc:// URI {:?}", uri);
+    let (server_name, media_id) = uri.strip_prefix("mxc://")
+        .and_then(|rest| {
+            let mut parts = rest.splitn(2, '/');
+            Some((parts.next()?, parts.next()?))
+        })
+        .ok_or_else(invalid)?;
+    if server_name.is_empty() || media_id.is_empty() || media_id.contains('/') {
+        return Err(invalid());
+    }
+    Ok((server_name.to_string(), media_id.to_string()))
+}
+
+async fn run_registration_tokens(
+    command: RegistrationTokensCommand,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        RegistrationTokensCommand::List { valid, invalid } => {
+            let request = assign!(synadminctl::list_registration_tokens::Request::new(), {
+                valid: if valid { Some(true) } else if invalid { Some(false) } else { None },
+            });
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+        },
+        RegistrationTokensCommand::Show { token } => {
+            let request = synadminctl::get_registration_token::Request::new(token);
+            let response = service.call(request).await?;
+            output::print(output_format, &response.registration_token)?;
+        },
+        RegistrationTokensCommand::Create { count, token, length, uses_allowed, valid_for } => {
+            if count != 1 && token.is_some() {
+                anyhow::bail!("--token can only be used when creating a single token");
+            }
+            let expiry_time = valid_for
+                .map(milliseconds_from_now)
+                .transpose()?;
+
+            let mut registration_tokens = vec![];
+            for _ in 0..count {
+                let request = assign!(synadminctl::create_registration_token::Request::new(), {
+                    token: token.clone(),
+                    length,
+                    uses_allowed,
+                    expiry_time,
                 });
                 let response = service.call(request).await?;
-                println!("{:#?}", response);
-                Ok(())
-            },
-            Opt::ListRooms { from } => {
-                println!("rooms from {}", from);
-                let request = assign!(synadminctl::list_rooms::Request::new(), {
-                    from: Some(from),
+                registration_tokens.push(response.registration_token);
+            }
+            output::print(output_format, &registration_tokens)?;
+        },
+        RegistrationTokensCommand::Update { token, uses_allowed, unlimited_uses, valid_for, never_expire } => {
+            let expiry_time = valid_for
+                .map(milliseconds_from_now)
+                .transpose()?;
+            let request = assign!(synadminctl::update_registration_token::Request::new(token), {
+                uses_allowed: if unlimited_uses { Some(None) } else { uses_allowed.map(Some) },
+                expiry_time: if never_expire { Some(None) } else { expiry_time.map(Some) },
+            });
+            let response = service.call(request).await?;
+            output::print(output_format, &response.registration_token)?;
+        },
+        RegistrationTokensCommand::Expire { tokens } => {
+            let now = milliseconds_since_epoch(std::time::SystemTime::now())?;
+            let mut registration_tokens = vec![];
+            for token in tokens {
+                let request = assign!(synadminctl::update_registration_token::Request::new(token), {
+                    expiry_time: Some(Some(now)),
                 });
                 let response = service.call(request).await?;
-                println!("{:#?}", response);
-                Ok(())
-            },
-            Opt::PurgeRoom { room_id } => {
-                println!("room purging");
-                let request = synadminctl::purge_room::Request::new(
-                    room_id.try_into()?,
-                );
+                registration_tokens.push(response.registration_token);
+            }
+            output::print(output_format, &registration_tokens)?;
+        },
+        RegistrationTokensCommand::Delete { tokens } => {
+            for token in tokens {
+                let request = synadminctl::delete_registration_token::Request::new(token.clone());
+                service.call(request).await?;
+                eprintln!("deleted registration token {}", token);
+            }
+        },
+    }
+    Ok(())
+}
+
+async fn run_devices(
+    command: DevicesCommand,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        DevicesCommand::List { user_id } => {
+            let request = synadminctl::list_devices::Request::new(user_id.try_into()?);
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+        },
+        DevicesCommand::Show { user_id, device_id } => {
+            let request = synadminctl::get_device::Request::new(user_id.try_into()?, device_id);
+            let response = service.call(request).await?;
+            output::print(output_format, &response.device)?;
+        },
+        DevicesCommand::Rename { user_id, device_id, display_name } => {
+            let request = synadminctl::update_device::Request::new(user_id.try_into()?, device_id, display_name);
+            service.call(request).await?;
+        },
+        DevicesCommand::Delete { user_id, device_ids } => {
+            let request = synadminctl::delete_devices::Request::new(user_id.try_into()?, device_ids.clone());
+            service.call(request).await?;
+            eprintln!("deleted {} devices", device_ids.len());
+        },
+        DevicesCommand::Wipe { user_id, except } => {
+            let user_id: ruma::UserId = user_id.try_into()?;
+            let response = service.call(synadminctl::list_devices::Request::new(user_id.clone())).await?;
+            // a typo in a device to keep would otherwise delete all devices
+            for device_id in &except {
+                if !response.devices.iter().any(|device| &device.device_id == device_id) {
+                    anyhow::bail!("{} has no device {}", user_id, device_id);
+                }
+            }
+
+            let (kept, deleted): (Vec<_>, Vec<_>) = response.devices.into_iter()
+                .partition(|device| except.contains(&device.device_id));
+            if !deleted.is_empty() {
+                let device_ids = deleted.iter().map(|device| device.device_id.clone()).collect();
+                service.call(synadminctl::delete_devices::Request::new(user_id, device_ids)).await?;
+            }
+            eprintln!("deleted {} devices, kept {}", deleted.len(), kept.len());
+            output::print(output_format, &deleted)?;
+        },
+    }
+    Ok(())
+}
+
+async fn run_media(
+    command: MediaCommand,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        MediaCommand::Room { room_id } => {
+            let request = synadminctl::room_media::Request::new(room_id.try_into()?);
+            let response = service.call(request).await?;
+            eprintln!("{} local and {} remote media", response.local.len(), response.remote.len());
+            output::print(output_format, &response)?;
+        },
+        MediaCommand::List { user_id, from, limit, all } => {
+            let request = assign!(synadminctl::list_user_media::Request::new(user_id.try_into()?), {
+                from,
+                limit,
+            });
+            let (media, total) = if all {
+                let media: Vec<_> = synadminctl::paginate(&service, request).try_collect().await?;
+                let total = media.len() as u64;
+                (media, total)
+            } else {
                 let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
-            },
-            Opt::ResetPassword { user_id, logout_devices } => {
-                // could also prompt on stderr, should I?
-                // TODO: option for random generation
-                let new_password = rpassword::prompt_password_stdout("new password: ").unwrap();
-
-                let request = synadminctl::reset_password::Request::new(
-                    user_id.try_into()?,
-                    new_password,
-                    Some(logout_devices),
-                );
+                (response.media, response.total.into())
+            };
+            let bytes: u64 = media.iter().map(|media| u64::from(media.media_length)).sum();
+            eprintln!("{} of {} media, {} in total", media.len(), total, output::size(bytes));
+            output::print(output_format, &media)?;
+        },
+        MediaCommand::Delete { mxc_uris } => {
+            // all URIs are checked before anything is deleted
+            let media = mxc_uris.iter()
+                .map(|uri| parse_mxc_uri(uri))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let mut responses = vec![];
+            for (server_name, media_id) in media {
+                let request = synadminctl::delete_media::Request::new(server_name, media_id);
+                responses.push(service.call(request).await?);
+            }
+            let deleted: u64 = responses.iter().map(|response| u64::from(response.total)).sum();
+            eprintln!("deleted {} media", deleted);
+            output::print(output_format, &responses)?;
+        },
+        MediaCommand::DeleteUserMedia { user_id, limit } => {
+            let request = assign!(synadminctl::delete_user_media::Request::new(user_id.try_into()?), {
+                limit,
+            });
+            let response = service.call(request).await?;
+            eprintln!("deleted {} media", response.total);
+            output::print(output_format, &response)?;
+        },
+        MediaCommand::DeleteLocal { server_name, older_than, larger_than, include_profiles } => {
+            let request = assign!(synadminctl::delete_local_media::Request::new(server_name, milliseconds_ago(older_than)?), {
+                size_gt: larger_than,
+                keep_profiles: Some(!include_profiles),
+            });
+            let response = service.call(request).await?;
+            eprintln!("deleted {} media", response.total);
+            output::print(output_format, &response)?;
+        },
+        MediaCommand::PurgeRemoteCache { older_than } => {
+            let request = synadminctl::purge_media_cache::Request::new(milliseconds_ago(older_than)?);
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+        },
+        MediaCommand::Quarantine => run_media_action(MediaAction::Quarantine, service, output_format).await?,
+        MediaCommand::Unquarantine => run_media_action(MediaAction::Unquarantine, service, output_format).await?,
+        MediaCommand::Protect => run_media_action(MediaAction::Protect, service, output_format).await?,
+        MediaCommand::Unprotect => run_media_action(MediaAction::Unprotect, service, output_format).await?,
+        MediaCommand::QuarantineRoom { room_id } => {
+            let request = synadminctl::quarantine_room_media::Request::new(room_id.try_into()?);
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+        },
+        MediaCommand::QuarantineUser { user_id } => {
+            let request = synadminctl::quarantine_user_media::Request::new(user_id.try_into()?);
+            let response = service.call(request).await?;
+            output::print(output_format, &response)?;
+        },
+    }
+    Ok(())
+}
+
+async fn media_action(
+    action: MediaAction,
+    service: &synadminctl::MatrixService<HttpService>,
+    local_server_name: &str,
+    mxc_uri: &str,
+) -> anyhow::Result<()> {
+    let (server_name, media_id) = parse_mxc_uri(mxc_uri)?;
+    let is_protection = matches!(action, MediaAction::Protect | MediaAction::Unprotect);
+    // synapse only takes the media ID and would protect a local media with the same ID instead
+    if is_protection && server_name != local_server_name {
+        anyhow::bail!("only local media of {} can be protected, not media of {}", local_server_name, server_name);
+    }
+    match action {
+        MediaAction::Quarantine => {
+            service.call(synadminctl::quarantine_media::Request::new(server_name, media_id)).await?;
+        },
+        MediaAction::Unquarantine => {
+            service.call(synadminctl::unquarantine_media::Request::new(server_name, media_id)).await?;
+        },
+        MediaAction::Protect => {
+            service.call(synadminctl::protect_media::Request::new(media_id)).await?;
+        },
+        MediaAction::Unprotect => {
+            service.call(synadminctl::unprotect_media::Request::new(media_id)).await?;
+        },
+    }
+    Ok(())
+}
+
+/// Applies the action to all mxc:// URIs from stdin, continuing after failures
+async fn run_media_action(
+    action: MediaAction,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    let input = unblock!(read_stdin())?;
+    // the server of the logged in admin is the local homeserver
+    let whoami = service.call(ruma::api::client::r0::account::whoami::Request::new()).await?;
+    let local_server_name = whoami.user_id.server_name().to_string();
+    let mut results = vec![];
+    for mxc_uri in input.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let result = media_action(action, service, &local_server_name, mxc_uri).await;
+        results.push(MediaResult {
+            mxc_uri: mxc_uri.to_string(),
+            error: result.err().map(|error| error.to_string()),
+        });
+    }
+    output::print(output_format, &results)?;
+
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} media failed", failed, results.len());
+    }
+    Ok(())
+}
+
+async fn run_reports(
+    command: ReportsCommand,
+    service: &synadminctl::MatrixService<HttpService>,
+    output_format: output::OutputFormat,
+) -> anyhow::Result<()> {
+    match command {
+        ReportsCommand::List { from, limit, all, room_id, user_id, oldest_first } => {
+            let request = assign!(synadminctl::event_reports::Request::new(), {
+                from,
+                limit,
+                room_id,
+                user_id,
+                dir: if oldest_first { Some(synadminctl::Direction::Forward) } else { None },
+            });
+            if all {
+                let reports: Vec<_> = synadminctl::paginate(&service, request).try_collect().await?;
+                output::print(output_format, &reports)?;
+            } else {
                 let response = service.call(request).await?;
-                println!("{:?}", response);
-                Ok(())
-            },
-        };
-        result
-    })
+                output::print(output_format, &response)?;
+            }
+        },
+        ReportsCommand::Show { report_id, event } => {
+            let response = service.call(synadminctl::event_report::Request::new(report_id)).await?;
+            if event {
+                // the event is printed as is, as it has no fixed columns
+                println!("{}", serde_json::to_string_pretty(&response.details.event_json)?);
+            } else {
+                output::print(output_format, &response.details)?;
+            }
+        },
+    }
+    Ok(())
 }
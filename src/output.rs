@@ -0,0 +1,856 @@
+use serde::Serialize;
+use std::fmt::Display;
+
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Yaml,
+}
+
+impl OutputFormat {
+    pub const VARIANTS: &'static [&'static str] = &["table", "json", "csv", "yaml"];
+
+    /// Whether complete values are serialized, instead of printing rows of columns
+    pub fn is_structured(&self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Yaml)
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err(format!("unknown output format: {}", s)),
+        }
+    }
+}
+
+
+/// Column model of a resource, used for the table and csv output formats.
+///
+/// JSON and YAML output serialize the complete response instead,
+/// so e.g. pagination tokens are only visible there.
+pub trait Tabular {
+    fn columns() -> Vec<&'static str>;
+    fn rows(&self) -> Vec<Vec<String>>;
+}
+
+impl<T: Tabular> Tabular for Vec<T> {
+    fn columns() -> Vec<&'static str> {
+        T::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.iter().flat_map(Tabular::rows).collect()
+    }
+}
+
+
+pub fn print<T: Serialize + Tabular>(format: OutputFormat, value: &T) -> anyhow::Result<()> {
+    write(&mut std::io::stdout(), format, value)
+}
+
+/// Prints values without column model, only possible for structured output formats
+pub fn print_structured<T: Serialize>(format: OutputFormat, value: &T) -> anyhow::Result<()> {
+    write_structured(&mut std::io::stdout(), format, value)
+}
+
+fn write<T: Serialize + Tabular, W: std::io::Write>(writer: &mut W, format: OutputFormat, value: &T) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Table => write_table(writer, &T::columns(), &value.rows())?,
+        OutputFormat::Csv => {
+            let columns = T::columns();
+            // responses without content, e.g. from purge_room, have no columns to print
+            if columns.is_empty() {
+                return Ok(());
+            }
+            let mut writer = csv::Writer::from_writer(writer);
+            writer.write_record(&columns)?;
+            for row in value.rows() {
+                writer.write_record(&row)?;
+            }
+            writer.flush()?;
+        },
+        OutputFormat::Json | OutputFormat::Yaml => write_structured(writer, format, value)?,
+    }
+    Ok(())
+}
+
+fn write_structured<T: Serialize, W: std::io::Write>(writer: &mut W, format: OutputFormat, value: &T) -> anyhow::Result<()> {
+    match format {
+        OutputFormat::Json => writeln!(writer, "{}", serde_json::to_string_pretty(value)?)?,
+        OutputFormat::Yaml => write!(writer, "{}", serde_yaml::to_string(value)?)?,
+        OutputFormat::Table | OutputFormat::Csv => anyhow::bail!("this can only be printed as json or yaml"),
+    }
+    Ok(())
+}
+
+fn write_table<W: std::io::Write>(writer: &mut W, columns: &[&str], rows: &[Vec<String>]) -> std::io::Result<()> {
+    if columns.is_empty() {
+        return Ok(());
+    }
+
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = std::cmp::max(*width, cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = columns.iter().map(|column| column.to_uppercase()).collect();
+    writeln!(writer, "{}", table_line(&header, &widths))?;
+    for row in rows {
+        writeln!(writer, "{}", table_line(row, &widths))?;
+    }
+    Ok(())
+}
+
+fn table_line(cells: &[String], widths: &[usize]) -> String {
+    let cells: Vec<String> = cells.iter().zip(widths)
+        .map(|(cell, width)| format!("{:width$}", cell, width = width))
+        .collect();
+    cells.join("  ").trim_end().to_string()
+}
+
+
+fn optional<T: Display>(value: &Option<T>) -> String {
+    value.as_ref().map(ToString::to_string).unwrap_or_default()
+}
+
+/// Milliseconds since the unix epoch, as used by synapse, formatted as RFC 3339 timestamp.
+/// Times which cannot be formatted, e.g. after the year 9999, are shown as milliseconds.
+fn timestamp(milliseconds: js_int::UInt) -> String {
+    use std::fmt::Write;
+
+    let time = std::time::UNIX_EPOCH.checked_add(std::time::Duration::from_millis(milliseconds.into()));
+    let mut formatted = String::new();
+    match time {
+        // the Display impl fails instead of formatting years with more than four digits
+        Some(time) if write!(formatted, "{}", humantime::format_rfc3339_seconds(time)).is_ok() => formatted,
+        _ => milliseconds.to_string(),
+    }
+}
+
+/// Amount of bytes in binary units, e.g. 1.5 MiB
+pub fn size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn list<T: Display>(values: &[T]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+fn threepids(threepids: &Option<Vec<synadminctl::Threepid>>) -> String {
+    threepids.iter().flatten()
+        .map(|threepid| threepid.address.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+
+impl Tabular for synadminctl::version::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["server_version", "python_version"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.server_version.clone(), self.python_version.clone()]]
+    }
+}
+
+impl Tabular for synadminctl::user_is_admin::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["admin"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.admin.to_string()]]
+    }
+}
+
+impl Tabular for synadminctl::query_user::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["displayname", "threepids", "avatar_url", "admin", "deactivated"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            optional(&self.displayname),
+            threepids(&self.threepids),
+            optional(&self.avatar_url),
+            self.admin.to_string(),
+            self.deactivated.to_string(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_joined_rooms::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["room_id"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.joined_rooms.iter().map(|room_id| vec![room_id.to_string()]).collect()
+    }
+}
+
+impl Tabular for synadminctl::create_modify_account::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["name", "displayname", "threepids", "avatar_url", "admin", "is_guest", "deactivated", "creation_ts"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.name.to_string(),
+            optional(&self.displayname),
+            threepids(&self.threepids),
+            optional(&self.avatar_url),
+            self.admin.to_string(),
+            self.is_guest.to_string(),
+            self.deactivated.to_string(),
+            self.creation_ts.to_string(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_accounts::UserDetails {
+    fn columns() -> Vec<&'static str> {
+        vec!["name", "displayname", "admin", "is_guest", "deactivated", "user_type", "avatar_url"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.name.to_string(),
+            optional(&self.displayname),
+            self.admin.to_string(),
+            self.is_guest.to_string(),
+            self.deactivated.to_string(),
+            optional(&self.user_type),
+            optional(&self.avatar_url),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_accounts::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::list_accounts::UserDetails::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.users.rows()
+    }
+}
+
+impl Tabular for synadminctl::list_rooms::RoomDetails {
+    fn columns() -> Vec<&'static str> {
+        vec![
+            "room_id", "name", "canonical_alias", "joined_members", "joined_local_members", "version",
+            "creator", "encryption", "federatable", "public", "join_rules", "guest_access",
+            "history_visibility", "state_events",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.room_id.to_string(),
+            optional(&self.name),
+            optional(&self.canonical_alias),
+            self.joined_members.to_string(),
+            self.joined_local_members.to_string(),
+            self.version.clone(),
+            optional(&self.creator),
+            optional(&self.encryption),
+            self.federatable.to_string(),
+            self.public.to_string(),
+            optional(&self.join_rules),
+            optional(&self.guest_access),
+            optional(&self.history_visibility),
+            self.state_events.to_string(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_rooms::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::list_rooms::RoomDetails::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.rooms.rows()
+    }
+}
+
+impl Tabular for synadminctl::purge_room::Response {
+    fn columns() -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![]
+    }
+}
+
+impl Tabular for synadminctl::delete_room::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["delete_id"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.delete_id.clone()]]
+    }
+}
+
+impl Tabular for synadminctl::delete_room::DeleteStatus {
+    fn columns() -> Vec<&'static str> {
+        vec!["delete_id", "status", "error", "kicked_users", "failed_to_kick_users", "local_aliases", "new_room_id"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.delete_id.clone(),
+            self.status.as_str().to_string(),
+            optional(&self.error),
+            list(&self.shutdown_room.kicked_users),
+            list(&self.shutdown_room.failed_to_kick_users),
+            list(&self.shutdown_room.local_aliases),
+            optional(&self.shutdown_room.new_room_id),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::room_delete_status::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::delete_room::DeleteStatus::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.results.rows()
+    }
+}
+
+impl Tabular for synadminctl::reset_password::Response {
+    fn columns() -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![]
+    }
+}
+
+impl Tabular for crate::profiles::Profile {
+    fn columns() -> Vec<&'static str> {
+        vec!["name", "default", "encrypted", "user_id", "base_url", "error"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.name.clone(),
+            self.default.to_string(),
+            self.encrypted.to_string(),
+            optional(&self.user_id),
+            optional(&self.base_url),
+            optional(&self.error),
+        ]]
+    }
+}
+
+impl Tabular for crate::Whoami {
+    fn columns() -> Vec<&'static str> {
+        vec!["profile", "user_id", "device_id", "base_url"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            optional(&self.profile),
+            self.user_id.clone(),
+            self.device_id.clone(),
+            self.base_url.clone(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::registration_tokens::RegistrationToken {
+    fn columns() -> Vec<&'static str> {
+        vec!["token", "uses_allowed", "pending", "completed", "remaining_uses", "expiry_time"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.token.clone(),
+            self.uses_allowed.map_or("unlimited".to_string(), |uses_allowed| uses_allowed.to_string()),
+            self.pending.to_string(),
+            self.completed.to_string(),
+            self.remaining_uses().map_or("unlimited".to_string(), |remaining_uses| remaining_uses.to_string()),
+            self.expiry_time.map_or("never".to_string(), timestamp),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_registration_tokens::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::registration_tokens::RegistrationToken::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.registration_tokens.rows()
+    }
+}
+
+impl Tabular for synadminctl::devices::Device {
+    fn columns() -> Vec<&'static str> {
+        vec!["device_id", "display_name", "last_seen_ip", "last_seen_ts"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.device_id.clone(),
+            optional(&self.display_name),
+            optional(&self.last_seen_ip),
+            self.last_seen_ts.map(timestamp).unwrap_or_default(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_devices::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::devices::Device::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.devices.rows()
+    }
+}
+
+impl Tabular for synadminctl::deactivate_account::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["id_server_unbind_result"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.id_server_unbind_result.clone()]]
+    }
+}
+
+impl Tabular for synadminctl::room_media::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["mxc_uri", "origin"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let local = self.local.iter().map(|uri| vec![uri.clone(), "local".to_string()]);
+        let remote = self.remote.iter().map(|uri| vec![uri.clone(), "remote".to_string()]);
+        local.chain(remote).collect()
+    }
+}
+
+impl Tabular for synadminctl::list_user_media::Media {
+    fn columns() -> Vec<&'static str> {
+        vec![
+            "media_id", "media_type", "size", "upload_name", "created_ts", "last_access_ts",
+            "quarantined_by", "safe_from_quarantine",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.media_id.clone(),
+            self.media_type.clone(),
+            size(self.media_length.into()),
+            optional(&self.upload_name),
+            timestamp(self.created_ts),
+            self.last_access_ts.map(timestamp).unwrap_or_default(),
+            optional(&self.quarantined_by),
+            self.safe_from_quarantine.to_string(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::list_user_media::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::list_user_media::Media::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.media.rows()
+    }
+}
+
+fn deleted_media(deleted_media: &[String]) -> Vec<Vec<String>> {
+    deleted_media.iter().map(|media_id| vec![media_id.clone()]).collect()
+}
+
+impl Tabular for synadminctl::delete_user_media::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["deleted_media"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        deleted_media(&self.deleted_media)
+    }
+}
+
+impl Tabular for synadminctl::delete_media::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["deleted_media"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        deleted_media(&self.deleted_media)
+    }
+}
+
+impl Tabular for synadminctl::delete_local_media::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["deleted_media"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        deleted_media(&self.deleted_media)
+    }
+}
+
+impl Tabular for synadminctl::purge_media_cache::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["deleted"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.deleted.to_string()]]
+    }
+}
+
+impl Tabular for synadminctl::quarantine_room_media::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["num_quarantined"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.num_quarantined.to_string()]]
+    }
+}
+
+impl Tabular for synadminctl::quarantine_user_media::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["num_quarantined"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.num_quarantined.to_string()]]
+    }
+}
+
+impl Tabular for crate::MediaResult {
+    fn columns() -> Vec<&'static str> {
+        vec!["mxc_uri", "result"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.mxc_uri.clone(),
+            self.error.clone().unwrap_or_else(|| "ok".to_string()),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::event_reports::EventReport {
+    fn columns() -> Vec<&'static str> {
+        vec!["id", "received_ts", "reporter", "room_id", "room_name", "canonical_alias", "sender", "score", "reason", "event_id"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.id.to_string(),
+            timestamp(self.received_ts),
+            self.user_id.to_string(),
+            self.room_id.to_string(),
+            optional(&self.name),
+            optional(&self.canonical_alias),
+            self.sender.to_string(),
+            optional(&self.score),
+            optional(&self.reason),
+            self.event_id.to_string(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::event_reports::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::event_reports::EventReport::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.event_reports.rows()
+    }
+}
+
+// the event itself is only part of the json and yaml output, or shown with reports show --event
+impl Tabular for synadminctl::event_report::EventReportDetails {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::event_reports::EventReport::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.report.rows()
+    }
+}
+
+impl Tabular for synadminctl::room_details::Response {
+    fn columns() -> Vec<&'static str> {
+        let mut columns = synadminctl::list_rooms::RoomDetails::columns();
+        columns.extend(&["topic", "avatar", "room_type", "joined_local_devices"]);
+        columns
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = self.room.rows();
+        for row in &mut rows {
+            row.extend(vec![
+                optional(&self.room.topic),
+                optional(&self.room.avatar),
+                optional(&self.room.room_type),
+                optional(&self.room.joined_local_devices),
+            ]);
+        }
+        rows
+    }
+}
+
+impl Tabular for synadminctl::room_members::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["member"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.members.iter().map(|user_id| vec![user_id.to_string()]).collect()
+    }
+}
+
+impl Tabular for synadminctl::room_state::StateEvent {
+    fn columns() -> Vec<&'static str> {
+        vec!["type", "state_key", "sender", "origin_server_ts", "content"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.event_type.clone(),
+            self.state_key.clone(),
+            self.sender.to_string(),
+            timestamp(self.origin_server_ts),
+            self.content.to_string(),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::room_state::Response {
+    fn columns() -> Vec<&'static str> {
+        synadminctl::room_state::StateEvent::columns()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.state.rows()
+    }
+}
+
+impl Tabular for crate::RoomBlockStatus {
+    fn columns() -> Vec<&'static str> {
+        vec!["room", "room_id", "blocked", "blocked_by"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.room.clone(),
+            self.room_id.to_string(),
+            self.blocked.to_string(),
+            optional(&self.blocked_by),
+        ]]
+    }
+}
+
+impl Tabular for crate::JoinResult {
+    fn columns() -> Vec<&'static str> {
+        vec!["user_id", "room_id", "result"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.user_id.clone(),
+            optional(&self.room_id),
+            self.error.clone().unwrap_or_else(|| "joined".to_string()),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::purge_history::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["purge_id"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![self.purge_id.clone()]]
+    }
+}
+
+impl Tabular for crate::PurgeStatus {
+    fn columns() -> Vec<&'static str> {
+        vec!["purge_id", "status", "error"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.purge_id.clone(),
+            self.status.as_str().to_string(),
+            optional(&self.error),
+        ]]
+    }
+}
+
+impl Tabular for synadminctl::register::Response {
+    fn columns() -> Vec<&'static str> {
+        vec!["user_id", "home_server", "device_id"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.user_id.to_string(),
+            self.home_server.clone(),
+            self.device_id.clone(),
+        ]]
+    }
+}
+
+impl Tabular for crate::WhoisConnection {
+    fn columns() -> Vec<&'static str> {
+        vec!["user_id", "device_id", "ip", "last_seen", "user_agent", "ip_also_used_by"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.user_id.to_string(),
+            self.device_id.clone(),
+            self.ip.clone(),
+            timestamp(self.last_seen),
+            optional(&self.user_agent),
+            list(&self.ip_also_used_by),
+        ]]
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_formatting() {
+        assert_eq!(timestamp(js_int::UInt::new(1600000000000).unwrap()), "2020-09-13T12:26:40Z");
+        // after the year 9999
+        assert_eq!(timestamp(js_int::UInt::MAX), "9007199254740991");
+    }
+
+    #[derive(Serialize)]
+    struct File {
+        name: String,
+        size: u64,
+    }
+
+    impl Tabular for File {
+        fn columns() -> Vec<&'static str> {
+            vec!["name", "size"]
+        }
+
+        fn rows(&self) -> Vec<Vec<String>> {
+            vec![vec![self.name.clone(), size(self.size)]]
+        }
+    }
+
+    /// A response without content
+    #[derive(Serialize)]
+    struct Empty {}
+
+    impl Tabular for Empty {
+        fn columns() -> Vec<&'static str> {
+            vec![]
+        }
+
+        fn rows(&self) -> Vec<Vec<String>> {
+            vec![]
+        }
+    }
+
+    fn files() -> Vec<File> {
+        vec![
+            File { name: "a".to_string(), size: 1023 },
+            File { name: "longer name".to_string(), size: 1536 * 1024 },
+        ]
+    }
+
+    fn output<T: Serialize + Tabular>(format: OutputFormat, value: &T) -> String {
+        let mut output = vec![];
+        write(&mut output, format, value).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn table_columns_are_aligned() {
+        assert_eq!(output(OutputFormat::Table, &files()), "\
+NAME         SIZE
+a            1023 B
+longer name  1.5 MiB
+");
+        assert_eq!(output(OutputFormat::Table, &Vec::<File>::new()), "NAME  SIZE\n");
+        assert_eq!(output(OutputFormat::Table, &Empty {}), "");
+    }
+
+    #[test]
+    fn csv_output() {
+        assert_eq!(output(OutputFormat::Csv, &files()), "name,size\na,1023 B\nlonger name,1.5 MiB\n");
+        assert_eq!(output(OutputFormat::Csv, &Empty {}), "");
+    }
+
+    #[test]
+    fn structured() {
+        // complete values instead of formatted columns
+        let expected = serde_json::json!([
+            { "name": "a", "size": 1023 },
+            { "name": "longer name", "size": 1572864 },
+        ]);
+        let json: serde_json::Value = serde_json::from_str(&output(OutputFormat::Json, &files())).unwrap();
+        assert_eq!(json, expected);
+        let yaml: serde_json::Value = serde_yaml::from_str(&output(OutputFormat::Yaml, &files())).unwrap();
+        assert_eq!(yaml, expected);
+
+        assert!(write_structured(&mut Vec::<u8>::new(), OutputFormat::Table, &files()).is_err());
+        assert!(write_structured(&mut Vec::<u8>::new(), OutputFormat::Csv, &files()).is_err());
+    }
+
+    #[test]
+    fn size_units() {
+        assert_eq!(size(0), "0 B");
+        assert_eq!(size(1023), "1023 B");
+        assert_eq!(size(1024), "1.0 KiB");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(1536 * 1024), "1.5 MiB");
+        assert_eq!(size(1024 * 1024 * 1024), "1.0 GiB");
+        // there is no larger unit than TiB
+        assert_eq!(size(1024 * 1024 * 1024 * 1024 * 1024), "1024.0 TiB");
+    }
+}
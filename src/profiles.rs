@@ -0,0 +1,489 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use synadminctl::Session;
+
+
+const DEFAULT_PROFILE: &str = "default";
+
+/// Settings which are not specific to a profile, stored in `config.ron`
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+struct Config {
+    /// Profile used when none is given on the command line
+    default_profile: Option<String>,
+}
+
+/// A stored profile, as shown by `profiles list`
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub default: bool,
+    pub encrypted: bool,
+    /// not known for encrypted or unreadable profiles
+    pub user_id: Option<String>,
+    /// not known for encrypted or unreadable profiles
+    pub base_url: Option<String>,
+    /// why the profile could not be read, so that one broken profile doesn't hide all others
+    pub error: Option<String>,
+}
+
+/// A session encrypted with ChaCha20Poly1305, using a key derived from a passphrase with scrypt
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct EncryptedSession {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// A stored session, which might need a passphrase to be used
+#[derive(Clone, Debug)]
+pub enum StoredSession {
+    Plain(Session),
+    Encrypted(EncryptedSession),
+}
+
+
+/// $XDG_CONFIG_HOME/synadminctl, falling back to ~/.config/synadminctl.
+/// This is used on all platforms, also on macOS where `dirs::config_dir` would point to ~/Library/Application Support.
+fn config_dir() -> anyhow::Result<PathBuf> {
+    config_dir_from(std::env::var_os("XDG_CONFIG_HOME"), dirs::home_dir())
+        .ok_or_else(|| anyhow::anyhow!("could not determine the home directory"))
+}
+
+fn config_dir_from(xdg_config_home: Option<std::ffi::OsString>, home_dir: Option<PathBuf>) -> Option<PathBuf> {
+    // the XDG base directory specification says to ignore relative paths
+    let config_dir = match xdg_config_home.map(PathBuf::from) {
+        Some(config_dir) if config_dir.is_absolute() => config_dir,
+        _ => home_dir?.join(".config"),
+    };
+    Some(config_dir.join("synadminctl"))
+}
+
+fn profiles_dir() -> anyhow::Result<PathBuf> {
+    Ok(config_dir()?.join("profiles"))
+}
+
+fn profile_path(name: &str) -> anyhow::Result<PathBuf> {
+    // profile names end up in file names
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.') || name.starts_with('.') {
+        anyhow::bail!("invalid profile name {:?}, only letters, digits, '-', '_' and '.' are allowed", name);
+    }
+    Ok(profiles_dir()?.join(format!("{}.ron", name)))
+}
+
+/// Writes to a temporary file only readable by the current user, which then replaces the given file,
+/// so that the file is never readable by others or only partially written.
+fn write_file(path: &Path, content: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        // the directory only contains secrets, so others do not even need to see the profile names
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(parent)?;
+    }
+
+    let temporary_path = path.with_extension("tmp");
+    // a leftover from an aborted write might have other permissions
+    match std::fs::remove_file(&temporary_path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+        _ => {},
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let file = options.open(&temporary_path)?;
+    let mut buffer = std::io::BufWriter::new(file);
+    write!(&mut buffer, "{}", content)?;
+    buffer.into_inner()?.sync_all()?;
+
+    std::fs::rename(&temporary_path, path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path, file: &std::fs::File) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = file.metadata()?.permissions().mode();
+    // group access is just as much of a leak as world access
+    if mode & 0o077 != 0 {
+        anyhow::bail!(
+            "refusing to use {}, as it is accessible by other users. Restrict it with chmod 600 and consider revoking its access token",
+            path.display(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path, _file: &std::fs::File) -> anyhow::Result<()> {
+    Ok(())
+}
+
+
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<[u8; KEY_LENGTH]> {
+    let mut key = [0u8; KEY_LENGTH];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &scrypt::ScryptParams::recommended(), &mut key)
+        .map_err(|_| anyhow::anyhow!("could not derive key from passphrase"))?;
+    Ok(key)
+}
+
+impl EncryptedSession {
+    fn encrypt(session: &Session, passphrase: &str) -> anyhow::Result<EncryptedSession> {
+        use chacha20poly1305::aead::{Aead, NewAead};
+        use rand::Rng;
+
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill(&mut salt);
+        rand::thread_rng().fill(&mut nonce);
+
+        let key = derive_key(passphrase, &salt)?;
+        let cipher = chacha20poly1305::ChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(&key));
+        let plaintext = ron::ser::to_string(session)?;
+        let ciphertext = cipher.encrypt(chacha20poly1305::Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| anyhow::anyhow!("could not encrypt session"))?;
+
+        Ok(Self {
+            salt: salt.to_vec(),
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    fn decrypt(&self, passphrase: &str) -> anyhow::Result<Session> {
+        use chacha20poly1305::aead::{Aead, NewAead};
+
+        if self.nonce.len() != NONCE_LENGTH {
+            anyhow::bail!("encrypted session has an invalid nonce");
+        }
+        let key = derive_key(passphrase, &self.salt)?;
+        let cipher = chacha20poly1305::ChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(&key));
+        let plaintext = cipher.decrypt(chacha20poly1305::Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("could not decrypt session, wrong passphrase?"))?;
+        Ok(ron::de::from_bytes(&plaintext)?)
+    }
+}
+
+impl StoredSession {
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, StoredSession::Encrypted(_))
+    }
+
+    /// Asks for the passphrase of encrypted sessions
+    pub fn unlock(self, profile: &str) -> anyhow::Result<Session> {
+        match self {
+            StoredSession::Plain(session) => Ok(session),
+            StoredSession::Encrypted(encrypted_session) => {
+                let passphrase = passphrase(&format!("passphrase for profile {}: ", profile))?;
+                encrypted_session.decrypt(&passphrase)
+            },
+        }
+    }
+}
+
+/// Read from $SYNADMINCTL_PASSPHRASE, or prompted for
+pub fn passphrase(prompt: &str) -> anyhow::Result<String> {
+    match std::env::var("SYNADMINCTL_PASSPHRASE") {
+        Ok(passphrase) => Ok(passphrase),
+        Err(_) => Ok(rpassword::prompt_password_stderr(prompt)?),
+    }
+}
+
+
+fn load_config() -> anyhow::Result<Config> {
+    let path = config_dir()?.join("config.ron");
+    match std::fs::File::open(path) {
+        Ok(file) => Ok(ron::de::from_reader(std::io::BufReader::new(file))?),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn store_config(config: &Config) -> anyhow::Result<()> {
+    let serialized = ron::ser::to_string_pretty(config, ron::ser::PrettyConfig::default())?;
+    write_file(&config_dir()?.join("config.ron"), &serialized)
+}
+
+
+pub fn default_profile() -> anyhow::Result<String> {
+    Ok(load_config()?.default_profile.unwrap_or_else(|| DEFAULT_PROFILE.to_string()))
+}
+
+pub fn set_default_profile(name: &str) -> anyhow::Result<()> {
+    if !profile_path(name)?.exists() {
+        anyhow::bail!("there is no profile named {:?}", name);
+    }
+    let mut config = load_config()?;
+    config.default_profile = Some(name.to_string());
+    store_config(&config)
+}
+
+/// Returns `Ok(None)` if the profile does not exist yet
+pub fn load_session(name: &str) -> anyhow::Result<Option<StoredSession>> {
+    let path = profile_path(name)?;
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into()),
+    };
+    check_permissions(&path, &file)?;
+
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut std::io::BufReader::new(file), &mut content)?;
+    match ron::de::from_str(&content) {
+        Ok(session) => Ok(Some(StoredSession::Plain(session))),
+        Err(error) => match ron::de::from_str(&content) {
+            Ok(encrypted_session) => Ok(Some(StoredSession::Encrypted(encrypted_session))),
+            // the plain session error is the more helpful one, as most sessions are not encrypted
+            Err(_) => Err(error.into()),
+        },
+    }
+}
+
+/// Encrypts the session with the passphrase, if one is given
+pub fn store_session(name: &str, session: Session, passphrase: Option<&str>) -> anyhow::Result<Session> {
+    let serialized = match passphrase {
+        Some(passphrase) => {
+            let encrypted_session = EncryptedSession::encrypt(&session, passphrase)?;
+            ron::ser::to_string_pretty(&encrypted_session, ron::ser::PrettyConfig::default())?
+        },
+        None => ron::ser::to_string_pretty(&session, ron::ser::PrettyConfig::default())?,
+    };
+    write_file(&profile_path(name)?, &serialized)?;
+    Ok(session)
+}
+
+pub fn encrypt(name: &str) -> anyhow::Result<()> {
+    let stored_session = load_session(name)?
+        .ok_or_else(|| anyhow::anyhow!("there is no profile named {:?}", name))?;
+    if stored_session.is_encrypted() {
+        anyhow::bail!("profile {:?} is already encrypted", name);
+    }
+    let session = stored_session.unlock(name)?;
+
+    let passphrase = passphrase(&format!("new passphrase for profile {}: ", name))?;
+    if std::env::var("SYNADMINCTL_PASSPHRASE").is_err()
+        && rpassword::prompt_password_stderr("repeat passphrase: ")? != passphrase {
+        anyhow::bail!("passphrases do not match");
+    }
+    // the encrypted session is renamed over the plain one, so no plaintext copy is left behind
+    store_session(name, session, Some(&passphrase))?;
+    Ok(())
+}
+
+pub fn decrypt(name: &str) -> anyhow::Result<()> {
+    let session = load_session(name)?
+        .ok_or_else(|| anyhow::anyhow!("there is no profile named {:?}", name))?
+        .unlock(name)?;
+    store_session(name, session, None)?;
+    Ok(())
+}
+
+pub fn remove(name: &str) -> anyhow::Result<()> {
+    std::fs::remove_file(profile_path(name)?)
+        .map_err(|error| anyhow::anyhow!("could not remove profile {:?}: {}", name, error))?;
+
+    let mut config = load_config()?;
+    if config.default_profile.as_deref() == Some(name) {
+        config.default_profile = None;
+        store_config(&config)?;
+    }
+    Ok(())
+}
+
+pub fn list() -> anyhow::Result<Vec<Profile>> {
+    let entries = match std::fs::read_dir(profiles_dir()?) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(error.into()),
+    };
+
+    let default_profile = default_profile()?;
+    let mut profiles = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().map_or(true, |extension| extension != "ron") {
+            continue;
+        }
+        let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let profile = match load_session(&name) {
+            Ok(Some(StoredSession::Plain(session))) => Profile {
+                default: name == default_profile,
+                name,
+                encrypted: false,
+                user_id: Some(session.user_id),
+                base_url: Some(session.base_url),
+                error: None,
+            },
+            Ok(Some(StoredSession::Encrypted(_))) => Profile {
+                default: name == default_profile,
+                name,
+                encrypted: true,
+                user_id: None,
+                base_url: None,
+                error: None,
+            },
+            Ok(None) => continue,
+            Err(error) => Profile {
+                default: name == default_profile,
+                name,
+                encrypted: false,
+                user_id: None,
+                base_url: None,
+                error: Some(error.to_string()),
+            },
+        };
+        profiles.push(profile);
+    }
+    profiles.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(profiles)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for each test, as tests run in parallel
+    fn temporary_dir(test: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("synadminctl-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    fn session() -> Session {
+        Session {
+            base_url: "https://matrix.example.org".to_string(),
+            access_token: "secret_token".to_string(),
+            user_id: "@admin:example.org".to_string(),
+            device_id: "ABCDEFGH".to_string(),
+        }
+    }
+
+    #[test]
+    fn profile_names() {
+        for name in &["default", "example.org", "work-admin_2"] {
+            assert!(profile_path(name).unwrap().ends_with(format!("profiles/{}.ron", name)));
+        }
+        for name in &["", ".hidden", "..", "../config", "a/b", "a b", "a\\b"] {
+            assert!(profile_path(name).is_err(), "{:?} should be rejected", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temporary_dir("write-file");
+        let path = dir.join("profiles").join("default.ron");
+        write_file(&path, "first").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(std::fs::metadata(path.parent().unwrap()).unwrap().permissions().mode() & 0o777, 0o700);
+
+        // replacing a file readable by others doesn't keep its permissions
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write_file(&path, "second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(!path.with_extension("tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_permissions_rejects_shared_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temporary_dir("check-permissions");
+        let path = dir.join("default.ron");
+        write_file(&path, "").unwrap();
+        for &(mode, allowed) in &[(0o600, true), (0o400, true), (0o644, false), (0o640, false), (0o620, false), (0o604, false)] {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+            let file = std::fs::File::open(&path).unwrap();
+            assert_eq!(check_permissions(&path, &file).is_ok(), allowed, "mode {:o}", mode);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_dir_fallback() {
+        let home = Some(PathBuf::from("/home/alice"));
+        assert_eq!(config_dir_from(Some("/xdg".into()), home.clone()), Some(PathBuf::from("/xdg/synadminctl")));
+        assert_eq!(config_dir_from(None, home.clone()), Some(PathBuf::from("/home/alice/.config/synadminctl")));
+        // relative paths are invalid according to the XDG base directory specification
+        assert_eq!(config_dir_from(Some("xdg".into()), home.clone()), Some(PathBuf::from("/home/alice/.config/synadminctl")));
+        assert_eq!(config_dir_from(Some("".into()), home), Some(PathBuf::from("/home/alice/.config/synadminctl")));
+        assert_eq!(config_dir_from(Some("/xdg".into()), None), Some(PathBuf::from("/xdg/synadminctl")));
+        assert_eq!(config_dir_from(None, None), None);
+    }
+
+    /// The only test changing $XDG_CONFIG_HOME, as the environment is shared by all tests
+    #[test]
+    fn profiles_in_config_dir() {
+        let dir = temporary_dir("config-dir");
+        std::env::set_var("XDG_CONFIG_HOME", &dir);
+
+        assert_eq!(default_profile().unwrap(), "default");
+        assert!(list().unwrap().is_empty());
+
+        store_session("alpha", session(), None).unwrap();
+        store_session("beta", session(), Some("passphrase")).unwrap();
+        write_file(&profile_path("broken").unwrap(), "not a session").unwrap();
+        assert!(dir.join("synadminctl").join("profiles").join("alpha.ron").exists());
+
+        // one unreadable profile doesn't hide the others
+        let profiles = list().unwrap();
+        let names: Vec<_> = profiles.iter().map(|profile| profile.name.as_str()).collect();
+        assert_eq!(names, vec!["alpha", "beta", "broken"]);
+        assert_eq!(profiles[0].user_id.as_deref(), Some("@admin:example.org"));
+        assert!(!profiles[0].default && profiles[0].error.is_none());
+        assert!(profiles[1].encrypted && profiles[1].user_id.is_none() && profiles[1].error.is_none());
+        assert!(profiles[2].error.is_some());
+
+        set_default_profile("alpha").unwrap();
+        assert_eq!(default_profile().unwrap(), "alpha");
+        assert!(list().unwrap()[0].default);
+        assert!(set_default_profile("missing").is_err());
+        assert_eq!(default_profile().unwrap(), "alpha");
+
+        // removing the default profile falls back to the default name
+        remove("alpha").unwrap();
+        assert_eq!(default_profile().unwrap(), "default");
+        assert!(remove("alpha").is_err());
+        let names: Vec<_> = list().unwrap().into_iter().map(|profile| profile.name).collect();
+        assert_eq!(names, vec!["beta", "broken"]);
+
+        std::env::remove_var("XDG_CONFIG_HOME");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn encrypted_session() {
+        let encrypted_session = EncryptedSession::encrypt(&session(), "correct horse").unwrap();
+        let serialized = ron::ser::to_string(&encrypted_session).unwrap();
+        assert!(!serialized.contains("secret_token"));
+
+        let encrypted_session: EncryptedSession = ron::de::from_str(&serialized).unwrap();
+        assert_eq!(encrypted_session.decrypt("correct horse").unwrap(), session());
+        assert!(encrypted_session.decrypt("wrong horse").is_err());
+    }
+}